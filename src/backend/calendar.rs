@@ -0,0 +1,248 @@
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    backend::{data::DateRange, datetime::DEFAULT_TIME},
+    Error,
+};
+
+/// Upper bound on the length of a term, breaks included
+const MAX_WEEKS: u32 = 104;
+
+/// A term of an academic year.
+/// Week 1 is the week (Monday to Sunday) containing the start date.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Term {
+    pub name: String,
+    pub start: NaiveDate,
+    /// Number of teaching weeks
+    pub weeks: u32,
+    /// Weeks starting on one of these days are breaks, and are not counted as teaching weeks
+    pub breaks: Vec<DateRange>,
+}
+
+impl Term {
+    pub fn new(
+        name: String,
+        start: NaiveDate,
+        weeks: u32,
+        breaks: Vec<DateRange>,
+    ) -> Result<Self, Error> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err("Term names cannot be empty or contain spaces.".into());
+        }
+        if weeks == 0 || weeks > MAX_WEEKS {
+            return Err(format!("Terms must be between 1 and {} weeks long.", MAX_WEEKS).into());
+        }
+
+        let term = Self {
+            name,
+            start,
+            weeks,
+            breaks,
+        };
+        if term.week_start(weeks).is_none() {
+            return Err(format!(
+                "Terms cannot be longer than {} weeks, including breaks.",
+                MAX_WEEKS
+            )
+            .into());
+        }
+        Ok(term)
+    }
+
+    /// Monday of teaching week `week`, counting from 1
+    pub fn week_start(&self, week: u32) -> Option<NaiveDate> {
+        if week == 0 || week > self.weeks {
+            return None;
+        }
+
+        let first_monday = self
+            .start
+            .checked_sub_days(Days::new(self.start.weekday().num_days_from_monday() as u64))?;
+        let mut teaching = 0;
+        for offset in 0..MAX_WEEKS as u64 {
+            let monday = first_monday.checked_add_days(Days::new(7 * offset))?;
+            if self.breaks.iter().any(|range| range.contains(monday)) {
+                continue;
+            }
+            teaching += 1;
+            if teaching == week {
+                return Some(monday);
+            }
+        }
+        None
+    }
+
+    /// Last day of the term, the Sunday of its final teaching week
+    pub fn end(&self) -> Option<NaiveDate> {
+        self.week_start(self.weeks)?.checked_add_days(Days::new(6))
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} teaching weeks from {}", self.weeks, self.start)?;
+        if let Some(end) = self.end() {
+            write!(f, " to {}", end)?;
+        }
+        if !self.breaks.is_empty() {
+            let breaks = self
+                .breaks
+                .iter()
+                .map(DateRange::to_string)
+                .collect::<Vec<_>>();
+            write!(f, ", breaks: {}", breaks.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The terms of a guild, in date order
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct AcademicCalendar {
+    terms: Vec<Term>,
+}
+
+impl AcademicCalendar {
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Add a term, replacing any term with the same name
+    pub fn set_term(&mut self, term: Term) {
+        self.terms
+            .retain(|existing| !existing.name.eq_ignore_ascii_case(&term.name));
+        self.terms.push(term);
+        self.terms.sort_by_key(|term| term.start);
+    }
+
+    pub fn remove_term(&mut self, name: &str) -> Result<Term, Error> {
+        match self.position(name) {
+            Some(position) => Ok(self.terms.remove(position)),
+            None => Err(format!("There is no term called \"{}\".", name).into()),
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.terms
+            .iter()
+            .position(|term| term.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Finds a term by name, or by its number like "T2"
+    pub fn find(&self, term: &str) -> Option<&Term> {
+        if let Some(position) = self.position(term) {
+            return self.terms.get(position);
+        }
+        let number = term
+            .strip_prefix(['T', 't'])
+            .and_then(|n| n.parse::<usize>().ok())?;
+        self.terms.get(number.checked_sub(1)?)
+    }
+
+    /// Works out the date and time of a term-relative date
+    pub fn resolve(&self, date: &TermDate) -> Result<NaiveDateTime, Error> {
+        let Some(term) = self.find(&date.term) else {
+            return Err(format!("There is no term called \"{}\".", date.term).into());
+        };
+        let Some(monday) = term.week_start(date.week) else {
+            return Err(format!("{} only has {} teaching weeks.", term.name, term.weeks).into());
+        };
+
+        let day = monday
+            .checked_add_days(Days::new(date.weekday.num_days_from_monday() as u64))
+            .ok_or("Date is out of range.")?;
+        Ok(day.and_time(date.time))
+    }
+}
+
+/// A date relative to an academic term, such as "T1 W7 Fri 12:00".
+/// The weekday defaults to Monday, and the time is the same as for other dates without one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TermDate {
+    /// Term name or number, like "T1"
+    pub term: String,
+    pub week: u32,
+    pub weekday: Weekday,
+    pub time: NaiveTime,
+}
+
+impl FromStr for TermDate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || -> Error {
+            format!(
+                "Could not understand \"{}\", expected a term date like \"T1 W7 Fri 12:00\".",
+                s.trim()
+            )
+            .into()
+        };
+
+        let mut words = s.split_whitespace();
+        let term = words.next().ok_or_else(invalid)?.to_string();
+        let week = words
+            .next()
+            .and_then(|week| week.strip_prefix(['W', 'w']))
+            .and_then(|week| week.parse().ok())
+            .ok_or_else(invalid)?;
+
+        let mut weekday = Weekday::Mon;
+        let mut time = DEFAULT_TIME;
+        for word in words.by_ref().take(2) {
+            if let Ok(day) = word.parse::<Weekday>() {
+                weekday = day;
+            } else if let Ok(parsed) = NaiveTime::parse_from_str(word, "%H:%M") {
+                time = parsed;
+            } else {
+                return Err(invalid());
+            }
+        }
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            term,
+            week,
+            weekday,
+            time,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn term_dates() {
+        let mut calendar = AcademicCalendar::default();
+        // starts on a Wednesday, with reading week after week 5
+        let breaks = DateRange::parse_list("2024-10-28..2024-11-03").unwrap();
+        calendar.set_term(Term::new("Autumn".to_string(), date("2024-09-25"), 10, breaks).unwrap());
+        calendar.set_term(Term::new("Spring".to_string(), date("2025-01-13"), 10, vec![]).unwrap());
+
+        let resolve = |s: &str| {
+            let resolved = calendar.resolve(&s.parse::<TermDate>().unwrap())?;
+            Ok::<_, Error>(resolved.format("%Y-%m-%d %H:%M").to_string())
+        };
+        assert_eq!(resolve("T1 W1").unwrap(), "2024-09-23 09:00");
+        assert_eq!(resolve("T1 W5 Fri 17:00").unwrap(), "2024-10-25 17:00");
+        assert_eq!(resolve("autumn W6 Mon").unwrap(), "2024-11-04 09:00");
+        assert_eq!(resolve("T2 W7 Fri 12:00").unwrap(), "2025-02-28 12:00");
+        assert!(resolve("T1 W11").is_err());
+        assert!(resolve("T3 W1").is_err());
+        assert!("T1 Fri".parse::<TermDate>().is_err());
+    }
+}
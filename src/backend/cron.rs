@@ -0,0 +1,391 @@
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+use crate::{backend::data::MIN_REPEAT_MINUTES, Error};
+
+/// How far ahead to look for a matching time.
+/// Long enough for rare schedules such as "29th of February on a Monday".
+const SEARCH_DAYS: u64 = 366 * 28;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron expression with the usual five fields: minute, hour, day of month, month, day of week.
+///
+/// Fields accept `*`, numbers, ranges, lists and steps, along with month and weekday names.
+/// The day of month can also be `L` for the last day or `LW` for the last weekday of the month,
+/// and the day of week can be `5L` (or `FRIL`) for the last Friday of the month.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct Cron {
+    /// The expression as written by the user
+    source: String,
+    minutes: u64,
+    hours: u32,
+    /// Bit 1 is the 1st of the month
+    days: u32,
+    /// Bit 1 is January
+    months: u16,
+    /// Bit 0 is Sunday
+    weekdays: u8,
+    /// Last day of the month
+    last_day: bool,
+    /// Last Monday-Friday of the month
+    last_weekday: bool,
+    /// Weekdays that only match the last time they occur in the month
+    last_weekdays: u8,
+    /// Whether the day of month and day of week fields were anything other than `*`
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    /// Whether the input is meant as a cron expression: a macro like "@daily",
+    /// or five fields where the minute and hour are numeric
+    pub fn looks_like(s: &str) -> bool {
+        let s = s.trim();
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let numeric = |field: &str| {
+            field
+                .chars()
+                .all(|c| c.is_ascii_digit() || "*,-/".contains(c))
+        };
+        s.starts_with('@') || (fields.len() == 5 && fields[..2].iter().all(|field| numeric(field)))
+    }
+
+    /// First matching time at or after `from`, ignoring seconds
+    pub fn next_from(&self, from: NaiveDateTime) -> Option<NaiveDateTime> {
+        // round up to the next whole minute
+        let from = if from.second() == 0 && from.nanosecond() == 0 {
+            from
+        } else {
+            from.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1)
+        };
+
+        let first_day = from.date();
+        for offset in 0..SEARCH_DAYS {
+            let date = first_day.checked_add_days(Days::new(offset))?;
+            if !self.matches_date(date) {
+                continue;
+            }
+
+            // only the first day can have times before `from`
+            let earliest = if offset == 0 {
+                from.time()
+            } else {
+                NaiveTime::MIN
+            };
+            if let Some(time) = self.first_time_from(earliest) {
+                return Some(date.and_time(time));
+            }
+        }
+        None
+    }
+
+    fn first_time_from(&self, earliest: NaiveTime) -> Option<NaiveTime> {
+        for hour in earliest.hour()..24 {
+            if self.hours & (1 << hour) == 0 {
+                continue;
+            }
+            let first_minute = if hour == earliest.hour() {
+                earliest.minute()
+            } else {
+                0
+            };
+            for minute in first_minute..60 {
+                if self.minutes & (1 << minute) != 0 {
+                    return NaiveTime::from_hms_opt(hour, minute, 0);
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+
+        let last_of_month = last_day_of_month(date);
+        let weekday = date.weekday().num_days_from_sunday();
+
+        let day_matches = self.days & (1 << date.day()) != 0
+            || (self.last_day && date == last_of_month)
+            || (self.last_weekday && date == last_weekday_of_month(last_of_month));
+        let weekday_matches = self.weekdays & (1 << weekday) != 0
+            || (self.last_weekdays & (1 << weekday) != 0
+                && date.checked_add_days(Days::new(7)).map(|d| d.month()) != Some(date.month()));
+
+        // same as most cron implementations: when both fields are restricted, either can match
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day_matches || weekday_matches,
+            _ => day_matches && weekday_matches,
+        }
+    }
+
+    /// Checks that two matching minutes in an hour are never too close together
+    fn check_spacing(&self) -> Result<(), Error> {
+        let set = (0..60)
+            .filter(|minute| self.minutes & (1 << minute) != 0)
+            .collect::<Vec<u32>>();
+        let too_close = set.windows(2).any(|w| w[1] - w[0] < MIN_REPEAT_MINUTES)
+            // wrapping around into the next hour
+            || (set.len() > 1 && 60 - set[set.len() - 1] + set[0] < MIN_REPEAT_MINUTES);
+
+        if too_close {
+            return Err(format!(
+                "Cron repeats must be at least {} minutes apart.",
+                MIN_REPEAT_MINUTES
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|first| first.pred_opt())
+        .unwrap_or(date)
+}
+
+fn last_weekday_of_month(last_of_month: NaiveDate) -> NaiveDate {
+    let back = match last_of_month.weekday().num_days_from_monday() {
+        // Saturday and Sunday
+        5 => 1,
+        6 => 2,
+        _ => 0,
+    };
+    last_of_month
+        .checked_sub_days(Days::new(back))
+        .unwrap_or(last_of_month)
+}
+
+/// Parses a single field into a bitmask, with bit `n` set if the value `n` matches
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, Error> {
+    let invalid = || -> Error { format!("Invalid cron field \"{}\".", field).into() };
+    let value = |s: &str| -> Result<u32, Error> {
+        let n = match names.iter().position(|name| s.eq_ignore_ascii_case(name)) {
+            // names start from the lowest value of the field
+            Some(i) => i as u32 + min,
+            None => s.parse().map_err(|_| invalid())?,
+        };
+        if n < min || n > max {
+            return Err(format!("{} is out of range {}-{} in cron field.", n, min, max).into());
+        }
+        Ok(n)
+    };
+
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (item, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // "5/15" means starting at 5, every 15
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let n = value(range)?;
+                    (n, n)
+                }
+            },
+        };
+        if start > end {
+            return Err(invalid());
+        }
+
+        for n in (start..=end).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// Parses a day of week field, where both 0 and 7 are Sunday
+fn parse_weekdays(field: &str) -> Result<u8, Error> {
+    let mask = parse_field(field, 0, 7, &WEEKDAY_NAMES)?;
+    Ok((mask & 0x7f) as u8 | (mask >> 7) as u8)
+}
+
+impl FromStr for Cron {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = s.trim();
+        let expanded = match source.to_lowercase().as_str() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            _ => source,
+        };
+
+        let fields = expanded.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err("Cron expressions need 5 fields: minute hour day month weekday.".into());
+        };
+        // "?" is sometimes used instead of "*" for the day fields
+        let day = if day == "?" { "*" } else { day };
+        let weekday = if weekday == "?" { "*" } else { weekday };
+
+        let mut cron = Cron {
+            source: source.to_string(),
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])? as u32,
+            days: 0,
+            months: parse_field(month, 1, 12, &MONTH_NAMES)? as u16,
+            weekdays: 0,
+            last_day: false,
+            last_weekday: false,
+            last_weekdays: 0,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        };
+
+        for item in day.split(',') {
+            match item.to_uppercase().as_str() {
+                "L" => cron.last_day = true,
+                "LW" => cron.last_weekday = true,
+                _ => cron.days |= parse_field(item, 1, 31, &[])? as u32,
+            }
+        }
+
+        for item in weekday.split(',') {
+            let upper = item.to_uppercase();
+            match upper.strip_suffix('L') {
+                Some(day) if !day.is_empty() => cron.last_weekdays |= parse_weekdays(day)?,
+                _ => cron.weekdays |= parse_weekdays(item)?,
+            }
+        }
+
+        cron.check_spacing()?;
+        Ok(cron)
+    }
+}
+
+impl Display for Cron {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn next(cron: &str, from: &str) -> NaiveDateTime {
+        cron.parse::<Cron>().unwrap().next_from(at(from)).unwrap()
+    }
+
+    #[test]
+    fn next_from_includes_start() {
+        // 2024-01-31 is a Wednesday
+        assert_eq!(
+            next("0 9 * * MON,WED", "2024-01-31 09:00:00"),
+            at("2024-01-31 09:00:00")
+        );
+        // seconds round up to the next minute
+        assert_eq!(
+            next("0 9 * * MON,WED", "2024-01-31 09:00:30"),
+            at("2024-02-05 09:00:00")
+        );
+        assert_eq!(
+            next("*/15 9-10 * * *", "2024-01-31 10:50:00"),
+            at("2024-02-01 09:00:00")
+        );
+        assert_eq!(
+            next("@daily", "2024-01-31 09:00:00"),
+            at("2024-02-01 00:00:00")
+        );
+    }
+
+    #[test]
+    fn last_days() {
+        assert_eq!(
+            next("0 9 L * *", "2024-02-01 00:00:00"),
+            at("2024-02-29 09:00:00")
+        );
+        // 2024-03-31 is a Sunday
+        assert_eq!(
+            next("0 9 LW * *", "2024-03-01 00:00:00"),
+            at("2024-03-29 09:00:00")
+        );
+        assert_eq!(
+            next("0 9 * * 5L", "2024-01-01 00:00:00"),
+            at("2024-01-26 09:00:00")
+        );
+        assert_eq!(
+            next("0 9 * * FRIL", "2024-01-01 00:00:00"),
+            at("2024-01-26 09:00:00")
+        );
+    }
+
+    #[test]
+    fn sunday_is_0_or_7() {
+        for cron in ["0 9 * * 0", "0 9 * * 7", "0 9 * * SUN"] {
+            assert_eq!(next(cron, "2024-01-31 00:00:00"), at("2024-02-04 09:00:00"));
+        }
+    }
+
+    #[test]
+    fn day_or_weekday() {
+        // when both are restricted either matches: Friday the 5th, then Saturday the 13th
+        assert_eq!(
+            next("0 9 13 * FRI", "2024-01-01 00:00:00"),
+            at("2024-01-05 09:00:00")
+        );
+        assert_eq!(
+            next("0 9 13 * FRI", "2024-01-12 09:01:00"),
+            at("2024-01-13 09:00:00")
+        );
+        // otherwise both must match
+        assert_eq!(
+            next("0 9 13 1 *", "2024-01-14 00:00:00"),
+            at("2025-01-13 09:00:00")
+        );
+    }
+
+    #[test]
+    fn rejects_close_repeats() {
+        assert!("*/2 * * * *".parse::<Cron>().is_err());
+        assert!("0,3 * * * *".parse::<Cron>().is_err());
+        // 58 and 1 are only 3 minutes apart across the hour
+        assert!("1,58 * * * *".parse::<Cron>().is_err());
+        assert!("*/5 * * * *".parse::<Cron>().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        assert!("60 * * * *".parse::<Cron>().is_err());
+        assert!("0 9 * * MON *".parse::<Cron>().is_err());
+        assert!("0 9 32 * *".parse::<Cron>().is_err());
+        assert!("0 9 5-1 * *".parse::<Cron>().is_err());
+    }
+
+    #[test]
+    fn recognises_cron_input() {
+        assert!(Cron::looks_like("0 9 * * MON,WED"));
+        assert!(Cron::looks_like("@weekly"));
+        assert!(!Cron::looks_like("every 2 weeks on monday"));
+        assert!(!Cron::looks_like("weekly"));
+    }
+}
@@ -1,24 +1,30 @@
-use crate::{Context, Error};
+use crate::{
+    backend::{
+        calendar::{AcademicCalendar, Term},
+        cron::Cron,
+        datetime,
+        rrule::RRule,
+    },
+    Context, Error,
+};
+use chrono::{LocalResult, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
 use poise::serenity_prelude::{
     ChannelId, FormattedTimestamp, FormattedTimestampStyle, GuildId, RoleId, Timestamp, UserId,
 };
 use serde::Deserialize;
 use serde::Serialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap},
     fmt::Display,
-    hash::Hash,
+    str::FromStr,
 };
 
 /// Possible times between repeats
 #[non_exhaustive]
-#[derive(
-    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
-)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) enum Interval {
-    #[cfg(debug_assertions)]
-    FiveMinutesly,
-    /// For testing purposes
+    Minutely,
     Hourly,
     Daily,
     Weekly,
@@ -26,6 +32,37 @@ pub(crate) enum Interval {
     Yearly,
 }
 
+impl Interval {
+    /// Singular name of the unit of time the interval is measured in
+    pub fn unit(&self) -> &'static str {
+        use Interval::*;
+
+        match self {
+            Minutely => "minute",
+            Hourly => "hour",
+            Daily => "day",
+            Weekly => "week",
+            Monthly => "month",
+            Yearly => "year",
+        }
+    }
+
+    /// Parses a unit of time such as "week" or "days"
+    pub fn from_unit(unit: &str) -> Option<Self> {
+        use Interval::*;
+
+        match unit.trim_end_matches('s') {
+            "minute" | "min" => Some(Minutely),
+            "hour" | "hr" => Some(Hourly),
+            "day" => Some(Daily),
+            "week" => Some(Weekly),
+            "month" => Some(Monthly),
+            "year" => Some(Yearly),
+            _ => None,
+        }
+    }
+}
+
 // The debug formatter is good enough for display here
 impl Display for Interval {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -33,68 +70,515 @@ impl Display for Interval {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, Serialize, Deserialize)]
+/// What to do with a reminder that became due while the bot was offline
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, poise::ChoiceParameter,
+)]
+pub(crate) enum MissedPolicy {
+    /// Send it as soon as possible, marked as late
+    #[default]
+    Late,
+    /// Skip to the next repeat, if there is one
+    Skip,
+    /// Remove the reminder
+    Discard,
+}
+
+impl Display for MissedPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Shortest time allowed between repeats, so a reminder cannot flood a channel
+pub(crate) const MIN_REPEAT_MINUTES: u32 = 5;
+
+/// Number of upcoming repeats looked at for the shortest time between them,
+/// as cron and RRULE repeats are not evenly spaced
+const GAP_SAMPLES: usize = 100;
+
+/// Most occurrences in a row that can be skipped, so a repeat that is always excluded still ends
+const MAX_SKIPPED: u32 = 100_000;
+
+/// An inclusive range of days, such as a holiday
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    pub fn new(start: NaiveDate, end: NaiveDate) -> Result<Self, Error> {
+        if end < start {
+            return Err(format!("{} is before {}.", end, start).into());
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    /// Parses a comma-separated list of dates and ranges, e.g. "2024-02-19..2024-02-23, 2024-03-01"
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, Error> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+/// Parses a single date "2024-03-01" or a range "2024-02-19..2024-02-23"
+impl FromStr for DateRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let date = |s: &str| -> Result<NaiveDate, Error> {
+            NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("\"{}\" is not a date like 2024-03-01.", s.trim()).into())
+        };
+        match s.split_once("..") {
+            Some((start, end)) => DateRange::new(date(start)?, date(end)?),
+            None => {
+                let day = date(s)?;
+                DateRange::new(day, day)
+            }
+        }
+    }
+}
+
+impl Display for DateRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{} to {}", self.start, self.end)
+        }
+    }
+}
+
+/// How the timestamps of a repeating reminder are worked out
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum Recurrence {
+    /// A fixed number of intervals apart, e.g. every 2 weeks
+    Every { interval: Interval, every: u32 },
+    /// Every time matching a cron expression
+    Cron(Cron),
+    /// Occurrences of an iCalendar recurrence rule
+    RRule(RRule),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) struct Repeat {
-    pub interval: Interval,
+    pub recurrence: Recurrence,
     index: u32,
+    /// Timestamp of the last repeat, needed by recurrences that are not evenly spaced
+    #[serde(default)]
+    last: Option<Timestamp>,
+    /// Stop repeating after this timestamp
+    #[serde(default)]
+    pub until: Option<Timestamp>,
+    /// Stop repeating after being sent this many times
+    #[serde(default)]
+    pub max_occurrences: Option<u32>,
+    /// Number of times the reminder has been sent
+    #[serde(default)]
+    sent: u32,
+    /// Days on which no repeats are sent
+    #[serde(default)]
+    pub skipped: Vec<DateRange>,
+    /// Also skip the holidays of the guild the reminder is in
+    #[serde(default)]
+    pub skip_holidays: bool,
+    /// Copy of the guild holidays, kept up to date by `ReminderTable`
+    #[serde(default)]
+    holidays: Vec<DateRange>,
+    /// Time zone the repeats are worked out in, so they keep the same local time across DST.
+    /// UTC if not set.
+    #[serde(default)]
+    pub time_zone: Option<Tz>,
 }
 
 impl Repeat {
-    pub fn new(interval: Interval) -> Self {
-        Self { interval, index: 0 }
+    /// Repeat every `every` intervals, e.g. every 2 weeks.
+    pub fn new(interval: Interval, every: u32) -> Result<Self, Error> {
+        if every == 0 {
+            return Err("Repeats must be at least 1 interval apart.".into());
+        }
+        if interval == Interval::Minutely && every < MIN_REPEAT_MINUTES {
+            return Err(format!(
+                "Repeats must be at least {} minutes apart.",
+                MIN_REPEAT_MINUTES
+            )
+            .into());
+        }
+
+        Ok(Self::from_recurrence(Recurrence::Every { interval, every }))
+    }
+
+    /// Repeat at every time matching a cron expression
+    pub fn cron(cron: Cron) -> Self {
+        Self::from_recurrence(Recurrence::Cron(cron))
+    }
+
+    /// Repeat at the occurrences of an iCalendar RRULE
+    pub fn rrule(rrule: RRule) -> Self {
+        Self::from_recurrence(Recurrence::RRule(rrule))
+    }
+
+    fn from_recurrence(recurrence: Recurrence) -> Self {
+        Self {
+            recurrence,
+            index: 0,
+            last: None,
+            until: None,
+            max_occurrences: None,
+            sent: 0,
+            skipped: Vec::new(),
+            skip_holidays: false,
+            holidays: Vec::new(),
+            time_zone: None,
+        }
+    }
+
+    fn zone(&self) -> Tz {
+        self.time_zone.unwrap_or(Tz::UTC)
+    }
+
+    fn to_local(&self, utc: NaiveDateTime) -> NaiveDateTime {
+        self.zone().from_utc_datetime(&utc).naive_local()
+    }
+
+    fn to_utc(&self, local: NaiveDateTime) -> Option<NaiveDateTime> {
+        let zone = self.zone();
+        match zone.from_local_datetime(&local) {
+            // the first of the two when clocks go back
+            LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => {
+                Some(datetime.naive_utc())
+            }
+            // skipped when clocks go forward, use the same time an hour later instead
+            LocalResult::None => zone
+                .from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+                .map(|datetime| datetime.naive_utc()),
+        }
     }
 
-    /// Increases the internal index for keeping track of how many times a timer has repeated
-    pub fn increment_index(&mut self) {
-        self.index += 1;
+    /// Number of times the reminder has been sent
+    pub fn sent(&self) -> u32 {
+        self.sent
     }
 
-    /// Retrieves the next timestamp accounting for repeats from an initial timestamp
+    /// Records that the reminder was sent for the current repeat
+    pub fn record_sent(&mut self) {
+        self.sent += 1;
+    }
+
+    /// Starts counting repeats again from the reminder's timestamp, e.g. after it was moved.
+    /// The number of times sent is kept, so limits still apply.
+    pub fn restart(&mut self) {
+        self.index = 0;
+        self.last = None;
+    }
+
+    /// Keeps the end date, limits, skipped days and progress of the repeat this one replaces
+    pub fn keep_settings(&mut self, old: &Repeat) {
+        self.until = old.until;
+        self.max_occurrences = old.max_occurrences;
+        self.sent = old.sent;
+        self.skipped = old.skipped.clone();
+        self.skip_holidays = old.skip_holidays;
+        self.time_zone = old.time_zone;
+    }
+
+    /// Whether the end date or the maximum number of occurrences has been reached.
+    /// Takes the initial timestamp, same as `Repeat::next`.
+    pub fn is_finished(&self, timestamp: &Timestamp) -> bool {
+        self.max_occurrences.is_some_and(|max| self.sent >= max)
+            || self.until.is_some_and(|until| self.next(timestamp) > until)
+    }
+
+    /// Increases the internal index for keeping track of how many times a timer has repeated.
+    /// Takes the initial timestamp, same as `Repeat::next`.
+    pub fn increment_index(&mut self, timestamp: &Timestamp) {
+        // skipped occurrences count towards the index too
+        let (index, _) = self.next_included(timestamp);
+        self.last = Some(self.next(timestamp));
+        self.index = index + 1;
+    }
+
+    /// Retrieves the next timestamp accounting for repeats from an initial timestamp.
+    /// Occurrences on skipped days and holidays are jumped over.
     pub fn next(&self, timestamp: &Timestamp) -> Timestamp {
+        let (_, next) = self.next_included(timestamp);
+        // returning the last timestamp again lets callers see that it does not move forward
+        let offset_date = next.unwrap_or(
+            self.last
+                .map_or(timestamp.naive_utc(), |last| last.naive_utc()),
+        );
+
+        Timestamp::from_unix_timestamp(offset_date.timestamp()).unwrap()
+    }
+
+    /// Whether repeats are not sent on a day
+    pub fn is_excluded(&self, date: NaiveDate) -> bool {
+        let holidays = match self.skip_holidays {
+            true => self.holidays.as_slice(),
+            false => &[],
+        };
+        self.skipped
+            .iter()
+            .chain(holidays)
+            .any(|range| range.contains(date))
+    }
+
+    /// The first occurrence that is not excluded, along with its index
+    fn next_included(&self, timestamp: &Timestamp) -> (u32, Option<NaiveDateTime>) {
+        let start = timestamp.naive_utc();
+        let mut index = self.index;
+        let mut last = self.last.map(|last| last.naive_utc());
+
+        for _ in 0..MAX_SKIPPED {
+            let Some(next) = self.occurrence(start, index, last) else {
+                return (index, None);
+            };
+            if !self.is_excluded(self.to_local(next).date()) {
+                return (index, Some(next));
+            }
+            // an occurrence that does not move forward would be skipped forever
+            if last.is_some_and(|last| next <= last) {
+                return (index, None);
+            }
+            index += 1;
+            last = Some(next);
+        }
+        (index, None)
+    }
+
+    /// Occurrence number `index` counting from `start`, where `last` is the occurrence before it.
+    /// All times are in UTC, days and longer are counted in the repeat's time zone.
+    fn occurrence(
+        &self,
+        start: NaiveDateTime,
+        index: u32,
+        last: Option<NaiveDateTime>,
+    ) -> Option<NaiveDateTime> {
         use chrono::Days;
+        use chrono::Duration;
         use chrono::Months;
-        use chrono::NaiveDateTime;
         use Interval::*;
 
-        let naive_date = timestamp.naive_utc();
+        match &self.recurrence {
+            Recurrence::Every { interval, every } => {
+                // number of intervals since the initial timestamp
+                let steps = *every as u64 * index as u64;
+                let local = self.to_local(start);
 
-        #[allow(unreachable_patterns)]
-        let offset_date = match self.interval {
-            #[cfg(debug_assertions)]
-            FiveMinutesly => NaiveDateTime::from_timestamp_opt(
-                timestamp.timestamp() + (300 * self.index as i64),
-                0,
+                match interval {
+                    // minutes and hours are a fixed length, whatever the clocks do
+                    Minutely => return start.checked_add_signed(Duration::minutes(steps as i64)),
+                    Hourly => return start.checked_add_signed(Duration::hours(steps as i64)),
+                    Daily => local.checked_add_days(Days::new(steps)),
+                    Weekly => local.checked_add_days(Days::new(7 * steps)),
+                    Monthly => u32::try_from(steps)
+                        .ok()
+                        .and_then(|months| local.checked_add_months(Months::new(months))),
+                    Yearly => u32::try_from(12 * steps)
+                        .ok()
+                        .and_then(|months| local.checked_add_months(Months::new(months))),
+                }
+            }
+            Recurrence::Cron(cron) => match last {
+                // the first match strictly after the last repeat
+                Some(last) => cron.next_from(self.to_local(last) + Duration::seconds(1)),
+                None => cron.next_from(self.to_local(start)),
+            },
+            Recurrence::RRule(rrule) => rrule.next_after(
+                self.to_local(start),
+                last.map(|last| self.to_local(last)),
+                self.zone(),
             ),
-            Hourly => NaiveDateTime::from_timestamp_opt(timestamp.timestamp(), 0),
-            Daily => naive_date.checked_add_days(Days::new(self.index as u64)),
-            Weekly => naive_date.checked_add_days(Days::new(7 * self.index as u64)),
-            Monthly => naive_date.checked_add_months(Months::new(self.index)),
-            Yearly => naive_date.checked_add_months(Months::new(12 * self.index)),
-            _ => None,
         }
-        .unwrap_or(naive_date);
+        .and_then(|local| self.to_utc(local))
+    }
 
-        Timestamp::from_unix_timestamp(offset_date.timestamp()).unwrap()
+    /// Increases the index until the next timestamp is after `now`.
+    /// Returns false if the interval does not move forward, so `now` can never be passed.
+    pub fn skip_past(&mut self, timestamp: &Timestamp, now: &Timestamp) -> bool {
+        let mut next = self.next(timestamp);
+        while next <= *now {
+            self.increment_index(timestamp);
+            let following = self.next(timestamp);
+            if following <= next {
+                return false;
+            }
+            next = following;
+        }
+        true
+    }
+
+    /// Fails for repeats that would never be sent,
+    /// like a cron for the 30th of February or an RRULE that ends before it starts
+    pub fn check_sent(&self, timestamp: &Timestamp) -> Result<(), Error> {
+        if self.upcoming(timestamp, 1).is_empty() {
+            return Err("This repeat would never be sent.".into());
+        }
+        Ok(())
+    }
+
+    /// Shortest time between two of the upcoming repeats, in minutes.
+    /// None if it is not sent again after the next one.
+    pub fn shortest_gap(&self, timestamp: &Timestamp) -> Option<u32> {
+        self.upcoming(timestamp, GAP_SAMPLES)
+            .windows(2)
+            .map(|pair| ((pair[1].unix_timestamp() - pair[0].unix_timestamp()) / 60) as u32)
+            .min()
+    }
+
+    /// The next `count` timestamps, starting with `Repeat::next`
+    pub fn upcoming(&self, timestamp: &Timestamp, count: usize) -> Vec<Timestamp> {
+        let mut repeat = self.clone();
+        let mut timestamps: Vec<Timestamp> = Vec::with_capacity(count);
+        while timestamps.len() < count && !repeat.is_finished(timestamp) {
+            // `next` falls back to the last time once there are no more
+            if repeat.next_included(timestamp).1.is_none() {
+                break;
+            }
+            let next = repeat.next(timestamp);
+            if timestamps.last().is_some_and(|last| next <= *last) {
+                break;
+            }
+            timestamps.push(next);
+            repeat.record_sent();
+            repeat.increment_index(timestamp);
+        }
+        timestamps
     }
 }
 
-// The index field should be ignored when comparing
-impl PartialEq for Repeat {
-    fn eq(&self, other: &Self) -> bool {
-        self.interval == other.interval
+/// Parses repeats such as "weekly", "fortnightly", "every 3 days", "every 6 months",
+/// a cron expression like "0 9 * * MON,WED" or an RRULE like "RRULE:FREQ=WEEKLY;BYDAY=TU,TH"
+impl FromStr for Repeat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Interval::*;
+
+        let input = s.trim();
+        let s = input.to_lowercase();
+        let (interval, every) = match s.as_str() {
+            "hourly" => (Hourly, 1),
+            "daily" => (Daily, 1),
+            "weekly" => (Weekly, 1),
+            "fortnightly" => (Weekly, 2),
+            "monthly" => (Monthly, 1),
+            "yearly" | "annually" => (Yearly, 1),
+            _ if s.starts_with("rrule:") || s.contains("freq=") => {
+                return Ok(Repeat::rrule(input.parse()?));
+            }
+            _ if Cron::looks_like(&s) => {
+                return Ok(Repeat::cron(input.parse()?));
+            }
+            _ => {
+                let invalid = || format!("Could not understand repeat \"{}\".", s);
+                let mut words = s.split_whitespace();
+                if words.next() != Some("every") {
+                    return Err(invalid().into());
+                }
+
+                // "every week" and "every 2 weeks" are both allowed
+                let (every, unit) = match (words.next(), words.next()) {
+                    (Some(unit), None) => (1, unit),
+                    (Some(n), Some(unit)) => (n.parse().map_err(|_| invalid())?, unit),
+                    _ => return Err(invalid().into()),
+                };
+                if words.next().is_some() {
+                    return Err(invalid().into());
+                }
+
+                let interval = Interval::from_unit(unit).ok_or_else(invalid)?;
+                (interval, every)
+            }
+        };
+
+        Repeat::new(interval, every)
+    }
+}
+
+impl Display for Repeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.recurrence {
+            Recurrence::Every { interval, every: 1 } => write!(f, "{}", interval),
+            Recurrence::Every { interval, every } => {
+                write!(f, "Every {} {}s", every, interval.unit())
+            }
+            Recurrence::Cron(cron) => write!(f, "Cron `{}`", cron),
+            Recurrence::RRule(rrule) => write!(f, "RRULE `{}`", rrule),
+        }
+    }
+}
+
+impl Repeat {
+    /// The recurrence as it would be given to a command, so it parses back to the same one
+    pub fn to_input(&self) -> String {
+        match &self.recurrence {
+            Recurrence::Every { interval, every } => {
+                format!("every {} {}s", every, interval.unit())
+            }
+            Recurrence::Cron(cron) => cron.to_string(),
+            Recurrence::RRule(rrule) => rrule.to_string(),
+        }
+    }
+}
+
+/// Short identifier for a reminder, unique across the whole table.
+/// Shown to users in base 36 so it stays easy to type.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub(crate) struct ReminderId(u32);
+
+impl ReminderId {
+    pub fn get(&self) -> u32 {
+        self.0
     }
 }
 
-// The index field should be ignored when hashing
-impl Hash for Repeat {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.interval.hash(state);
+impl Display for ReminderId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut digits = Vec::new();
+        let mut n = self.0;
+        loop {
+            digits.push(std::char::from_digit(n % 36, 36).unwrap());
+            n /= 36;
+            if n == 0 {
+                break;
+            }
+        }
+        write!(f, "{}", digits.iter().rev().collect::<String>())
     }
 }
 
-/// A reminder reminder containing a target timestamp and metadata at a minimum
-#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
+impl FromStr for ReminderId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().trim_start_matches('#');
+        match u32::from_str_radix(s, 36) {
+            Ok(n) if n != 0 => Ok(Self(n)),
+            _ => Err(format!("{} is not a valid reminder id.", s).into()),
+        }
+    }
+}
+
+/// A reminder reminder containing a target timestamp and metadata at a minimum.
+/// Reminders are identified by their id, see `Reminder::id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct Reminder {
+    /// Assigned when added to a `ReminderTable`
+    #[serde(default)]
+    id: ReminderId,
     registered_at: Timestamp,
     registered_by: UserId,
     /// Timestamp at which reminder is due
@@ -106,20 +590,24 @@ pub(crate) struct Reminder {
     pub roles: Option<Vec<RoleId>>,
     /// Main description/body text
     pub description: Option<String>,
-}
-
-// Reminders with the same target timestamp and repeat state should be considered the same.
-impl PartialEq for Reminder {
-    fn eq(&self, other: &Self) -> bool {
-        (self.target_date == other.target_date) && (self.repeating == other.repeating)
-    }
-}
-
-impl Hash for Reminder {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.target_date.hash(state);
-        self.repeating.hash(state);
-    }
+    /// Overrides the guild policy for when this reminder is missed
+    #[serde(default)]
+    pub missed: Option<MissedPolicy>,
+    /// Minutes before the deadline to also send the reminder, largest first
+    #[serde(default)]
+    pub lead_times: Vec<u32>,
+    /// Users sent personal reminders by DM, the creator if empty
+    #[serde(default)]
+    pub recipients: Vec<UserId>,
+    /// Users sent a copy of this reminder by DM
+    #[serde(default)]
+    pub subscribers: Vec<UserId>,
+    /// Open a thread off each message sent, to keep discussion of it together
+    #[serde(default)]
+    pub thread: bool,
+    /// When what it reminds of is due, for a snoozed early reminder
+    #[serde(default)]
+    pub deadline: Option<Timestamp>,
 }
 
 impl Display for Reminder {
@@ -127,8 +615,8 @@ impl Display for Reminder {
         writeln!(
             f,
             "{}eminder for {}{}",
-            if let Some(repeating) = self.repeating {
-                format!("{} r", repeating.interval)
+            if let Some(repeating) = &self.repeating {
+                format!("{} r", repeating)
             } else {
                 "R".to_string()
             },
@@ -136,7 +624,7 @@ impl Display for Reminder {
                 self.target_date,
                 Some(FormattedTimestampStyle::LongDateTime)
             ),
-            if let Some(repeating) = self.repeating {
+            if let Some(repeating) = &self.repeating {
                 format!(
                     " (next in {})",
                     FormattedTimestamp::new(
@@ -161,6 +649,7 @@ impl Reminder {
         description: Option<String>,
     ) -> Self {
         Self {
+            id: ReminderId::default(),
             registered_at: ctx.created_at(),
             registered_by: ctx.author().id,
             target_date,
@@ -177,95 +666,627 @@ impl Reminder {
             } else {
                 None
             },
+            missed: None,
+            lead_times: Vec::new(),
+            recipients: Vec::new(),
+            subscribers: Vec::new(),
+            thread: false,
+            deadline: None,
+        }
+    }
+
+    /// A one-off reminder made without a command, e.g. by snoozing a delivered reminder
+    pub fn one_off(
+        registered_by: UserId,
+        target_date: Timestamp,
+        name: Option<String>,
+        roles: Option<Vec<RoleId>>,
+        description: Option<String>,
+    ) -> Self {
+        Self {
+            id: ReminderId::default(),
+            registered_at: Timestamp::now(),
+            registered_by,
+            target_date,
+            repeating: None,
+            name,
+            roles: roles.filter(|roles| !roles.is_empty()),
+            description: description.filter(|description| !description.is_empty()),
+            missed: None,
+            lead_times: Vec::new(),
+            recipients: Vec::new(),
+            subscribers: Vec::new(),
+            thread: false,
+            deadline: None,
+        }
+    }
+
+    /// Parses a list of times to send the reminder early, like "1w, 1d, 1h"
+    pub fn parse_lead_times(s: &str) -> Result<Vec<u32>, Error> {
+        let mut lead_times = s
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(datetime::parse_minutes)
+            .collect::<Result<Vec<_>, _>>()?;
+        if lead_times.contains(&0) {
+            return Err("Early reminders must be at least 1 minute before.".into());
+        }
+        lead_times.sort_unstable_by(|a, b| b.cmp(a));
+        lead_times.dedup();
+        Ok(lead_times)
+    }
+
+    /// Early reminders of a repeat have to come after the repeat before,
+    /// as they are only queued once it has been sent
+    pub fn check_lead_times(&self) -> Result<(), Error> {
+        let (Some(repeat), Some(&longest)) = (&self.repeating, self.lead_times.first()) else {
+            return Ok(());
+        };
+        match repeat.shortest_gap(&self.target_date) {
+            Some(gap) if longest >= gap => Err(format!(
+                "Early reminders must be less than {} before, the time between repeats.",
+                datetime::format_minutes(gap)
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// When the reminder is next sent
+    pub fn next_due(&self) -> Timestamp {
+        match &self.repeating {
+            Some(repeat) => repeat.next(&self.target_date),
+            None => self.target_date,
+        }
+    }
+
+    /// Users a personal reminder is sent to
+    pub fn recipients(&self) -> Vec<UserId> {
+        match self.recipients.is_empty() {
+            true => vec![self.registered_by],
+            false => self.recipients.clone(),
         }
     }
 
     pub fn get_creation(&self) -> (Timestamp, UserId) {
         (self.registered_at, self.registered_by)
     }
+
+    pub fn id(&self) -> ReminderId {
+        self.id
+    }
+}
+
+/// Where a reminder is delivered: a guild channel, or a user's DMs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum ReminderKey {
+    Channel(GuildId, ChannelId),
+    User(UserId),
+}
+
+impl ReminderKey {
+    /// Guild the reminder belongs to, personal reminders have none
+    pub fn guild_id(&self) -> Option<GuildId> {
+        match self {
+            ReminderKey::Channel(guild_id, _) => Some(*guild_id),
+            ReminderKey::User(_) => None,
+        }
+    }
+}
+
+impl Display for ReminderKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReminderKey::Channel(guild_id, channel_id) => {
+                write!(f, "Guild {}, Channel {}", guild_id, channel_id)
+            }
+            ReminderKey::User(user_id) => write!(f, "User {}", user_id),
+        }
+    }
 }
 
-/// HashMap of reminders for each guild and channel pair
+/// HashMap of reminders for each guild and channel pair, and for each user.
+/// Reminders are identified by their id, so any number of them can share a timestamp.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub(crate) struct ReminderTable {
-    map: HashMap<(GuildId, ChannelId), HashSet<Reminder>>,
+    /// Left out when saving `TableSettings` on their own
+    #[serde(default)]
+    map: HashMap<(GuildId, ChannelId), Vec<Reminder>>,
+    /// Personal reminders of each user, delivered by DM
+    #[serde(default)]
+    personal: HashMap<UserId, Vec<Reminder>>,
+    /// Default missed reminder policy for each guild
+    #[serde(default)]
+    missed_policies: HashMap<GuildId, MissedPolicy>,
+    /// Last reminder id handed out
+    #[serde(default)]
+    last_id: u32,
+    /// Named holiday periods for each guild, skipped by reminders that opt in
+    #[serde(default)]
+    holidays: HashMap<GuildId, HashMap<String, DateRange>>,
+    /// Academic terms of each guild, for term-relative dates
+    #[serde(default)]
+    calendars: HashMap<GuildId, AcademicCalendar>,
+    /// Default time zone of each guild
+    #[serde(default)]
+    guild_time_zones: HashMap<GuildId, Tz>,
+    /// Time zones chosen by users, used instead of the guild's
+    #[serde(default)]
+    user_time_zones: HashMap<UserId, Tz>,
+    /// Users sent a copy of every reminder in a channel by DM
+    #[serde(default)]
+    channel_subscribers: HashMap<(GuildId, ChannelId), Vec<UserId>>,
+    /// Users who marked each occurrence of a reminder as done, by the unix timestamp it was due.
+    /// Kept after the reminder is removed, until `ACKNOWLEDGEMENT_DAYS` have passed.
+    #[serde(default)]
+    acknowledgements: HashMap<ReminderId, BTreeMap<i64, Vec<UserId>>>,
+}
+
+/// How long to remember who marked an occurrence of a reminder as done
+const ACKNOWLEDGEMENT_DAYS: i64 = 90;
+
+/// The fields of a `ReminderTable` other than its reminders, saved apart from them.
+/// Reads back as a `ReminderTable` with no reminders.
+#[derive(Serialize)]
+pub(crate) struct TableSettings<'a> {
+    missed_policies: &'a HashMap<GuildId, MissedPolicy>,
+    last_id: u32,
+    holidays: &'a HashMap<GuildId, HashMap<String, DateRange>>,
+    calendars: &'a HashMap<GuildId, AcademicCalendar>,
+    guild_time_zones: &'a HashMap<GuildId, Tz>,
+    user_time_zones: &'a HashMap<UserId, Tz>,
+    channel_subscribers: &'a HashMap<(GuildId, ChannelId), Vec<UserId>>,
+    acknowledgements: &'a HashMap<ReminderId, BTreeMap<i64, Vec<UserId>>>,
 }
 
 impl ReminderTable {
     pub fn new() -> Self {
         Self {
             map: HashMap::new(),
+            personal: HashMap::new(),
+            missed_policies: HashMap::new(),
+            last_id: 0,
+            holidays: HashMap::new(),
+            calendars: HashMap::new(),
+            guild_time_zones: HashMap::new(),
+            user_time_zones: HashMap::new(),
+            channel_subscribers: HashMap::new(),
+            acknowledgements: HashMap::new(),
         }
     }
 
-    /// Add a reminder to the table for a guild/channel pair
-    pub fn add_reminder(
+    fn next_id(&mut self) -> ReminderId {
+        self.last_id += 1;
+        ReminderId(self.last_id)
+    }
+
+    /// Everything but the reminders themselves, without copying them
+    pub fn settings(&self) -> TableSettings<'_> {
+        TableSettings {
+            missed_policies: &self.missed_policies,
+            last_id: self.last_id,
+            holidays: &self.holidays,
+            calendars: &self.calendars,
+            guild_time_zones: &self.guild_time_zones,
+            user_time_zones: &self.user_time_zones,
+            channel_subscribers: &self.channel_subscribers,
+            acknowledgements: &self.acknowledgements,
+        }
+    }
+
+    /// Puts back a reminder read from storage, keeping its id
+    pub fn insert_reminder(&mut self, key: ReminderKey, reminder: Reminder) {
+        let reminders = match key {
+            ReminderKey::Channel(guild_id, channel_id) => {
+                self.map.entry((guild_id, channel_id)).or_default()
+            }
+            ReminderKey::User(user_id) => self.personal.entry(user_id).or_default(),
+        };
+        reminders.push(reminder);
+    }
+
+    /// Policy applied to missed reminders in a guild, unless a reminder overrides it
+    pub fn missed_policy(&self, guild_id: GuildId) -> MissedPolicy {
+        self.missed_policies
+            .get(&guild_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_missed_policy(&mut self, guild_id: GuildId, policy: MissedPolicy) {
+        self.missed_policies.insert(guild_id, policy);
+    }
+
+    /// Holidays of a guild, in date order
+    pub fn holidays(&self, guild_id: GuildId) -> Vec<(&String, &DateRange)> {
+        let mut holidays = self
+            .holidays
+            .get(&guild_id)
+            .map(|holidays| holidays.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        holidays.sort_by_key(|(name, range)| (range.start, *name));
+        holidays
+    }
+
+    /// Add or replace a named holiday.
+    /// Returns the reminders that skip holidays, as they need to be scheduled again.
+    pub fn set_holiday(
         &mut self,
         guild_id: GuildId,
-        channel_id: ChannelId,
-        reminder: Reminder,
-    ) -> Result<(), Error> {
-        let Some(reminders) = self.get_reminders_mut(guild_id, channel_id) else {
-            let mut set = HashSet::new();
-            set.insert(reminder);
-            self.map.insert((guild_id, channel_id), set);
-            return Ok(());
+        name: String,
+        range: DateRange,
+    ) -> Vec<(ReminderKey, Reminder)> {
+        self.holidays
+            .entry(guild_id)
+            .or_default()
+            .insert(name, range);
+        self.sync_holidays(guild_id)
+    }
+
+    /// Remove a named holiday.
+    /// Returns the reminders that skip holidays, as they need to be scheduled again.
+    pub fn remove_holiday(
+        &mut self,
+        guild_id: GuildId,
+        name: &str,
+    ) -> Result<Vec<(ReminderKey, Reminder)>, Error> {
+        let removed = self
+            .holidays
+            .get_mut(&guild_id)
+            .and_then(|holidays| holidays.remove(name));
+        if removed.is_none() {
+            return Err(format!("There is no holiday called \"{}\".", name).into());
+        }
+        // there should never be an empty map in the hashmap
+        if self.holidays.get(&guild_id).is_some_and(HashMap::is_empty) {
+            self.holidays.remove(&guild_id);
+        }
+        Ok(self.sync_holidays(guild_id))
+    }
+
+    /// Time zone used for a user's input and reminders.
+    /// Their own time zone if set, then the guild's, then UTC.
+    pub fn time_zone(&self, guild_id: Option<GuildId>, user_id: UserId) -> Tz {
+        self.user_time_zones
+            .get(&user_id)
+            .or_else(|| self.guild_time_zones.get(&guild_id?))
+            .copied()
+            .unwrap_or(Tz::UTC)
+    }
+
+    pub fn guild_time_zone(&self, guild_id: GuildId) -> Option<Tz> {
+        self.guild_time_zones.get(&guild_id).copied()
+    }
+
+    pub fn user_time_zone(&self, user_id: UserId) -> Option<Tz> {
+        self.user_time_zones.get(&user_id).copied()
+    }
+
+    pub fn set_guild_time_zone(&mut self, guild_id: GuildId, time_zone: Tz) {
+        self.guild_time_zones.insert(guild_id, time_zone);
+    }
+
+    /// Set or clear the time zone of a user
+    pub fn set_user_time_zone(&mut self, user_id: UserId, time_zone: Option<Tz>) {
+        match time_zone {
+            Some(time_zone) => self.user_time_zones.insert(user_id, time_zone),
+            None => self.user_time_zones.remove(&user_id),
         };
+    }
 
-        reminders.insert(reminder);
-        Ok(())
+    pub fn calendar(&self, guild_id: GuildId) -> Option<&AcademicCalendar> {
+        self.calendars.get(&guild_id)
+    }
+
+    /// Add a term to a guild's calendar, replacing any term with the same name
+    pub fn set_term(&mut self, guild_id: GuildId, term: Term) {
+        self.calendars.entry(guild_id).or_default().set_term(term);
+    }
+
+    pub fn remove_term(&mut self, guild_id: GuildId, name: &str) -> Result<Term, Error> {
+        let Some(calendar) = self.calendars.get_mut(&guild_id) else {
+            return Err("No terms have been set for this server.".into());
+        };
+        let removed = calendar.remove_term(name)?;
+        // there should never be an empty calendar in the hashmap
+        if calendar.is_empty() {
+            self.calendars.remove(&guild_id);
+        }
+        Ok(removed)
     }
 
-    /// Remove a reminder already in the table
-    /// Requires a copy of the originally added reminder, or at least one with the same timestamp and repeat.
-    pub fn remove_reminder(
+    fn holiday_ranges(&self, guild_id: GuildId) -> Vec<DateRange> {
+        self.holidays
+            .get(&guild_id)
+            .map(|holidays| holidays.values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Copies the holidays of a guild into a reminder that skips them.
+    /// Personal reminders do not belong to a guild, so have no holidays.
+    fn apply_holidays(&self, key: ReminderKey, reminder: &mut Reminder) {
+        if let Some(repeat) = reminder.repeating.as_mut() {
+            repeat.holidays = match (repeat.skip_holidays, key.guild_id()) {
+                (true, Some(guild_id)) => self.holiday_ranges(guild_id),
+                _ => Vec::new(),
+            };
+        }
+    }
+
+    /// Updates every reminder in a guild that skips holidays, returning the ones changed
+    fn sync_holidays(&mut self, guild_id: GuildId) -> Vec<(ReminderKey, Reminder)> {
+        let holidays = self.holiday_ranges(guild_id);
+        let mut changed = Vec::new();
+        for (&(_, channel_id), reminders) in self
+            .map
+            .iter_mut()
+            .filter(|((reminder_guild, _), _)| *reminder_guild == guild_id)
+        {
+            for reminder in reminders.iter_mut() {
+                let Some(repeat) = reminder.repeating.as_mut() else {
+                    continue;
+                };
+                if repeat.skip_holidays {
+                    repeat.holidays = holidays.clone();
+                    changed.push((ReminderKey::Channel(guild_id, channel_id), reminder.clone()));
+                }
+            }
+        }
+        changed
+    }
+
+    fn reminders_mut(&mut self, key: ReminderKey) -> Option<&mut Vec<Reminder>> {
+        match key {
+            ReminderKey::Channel(guild_id, channel_id) => self.map.get_mut(&(guild_id, channel_id)),
+            ReminderKey::User(user_id) => self.personal.get_mut(&user_id),
+        }
+    }
+
+    /// Add a reminder to the table for a guild/channel pair or a user.
+    /// Returns the reminder as stored, with its id assigned.
+    pub fn add_reminder(
         &mut self,
-        guild_id: GuildId,
-        channel_id: ChannelId,
-        reminder: &Reminder,
-    ) -> Result<(), Error> {
-        let Some(reminders) = self.get_reminders_mut(guild_id, channel_id) else {
-            return Err(format!(
-                "Guild {}, Channel {} does not have any reminders set.",
-                guild_id, channel_id
-            )
-            .into());
+        key: ReminderKey,
+        mut reminder: Reminder,
+    ) -> Result<Reminder, Error> {
+        reminder.id = self.next_id();
+        self.apply_holidays(key, &mut reminder);
+        self.insert_reminder(key, reminder.clone());
+        Ok(reminder)
+    }
+
+    /// Remove a reminder already in the table by its id
+    pub fn remove_reminder(&mut self, key: ReminderKey, id: ReminderId) -> Result<Reminder, Error> {
+        let Some(reminders) = self.reminders_mut(key) else {
+            return Err(format!("{} does not have any reminders set.", key).into());
         };
 
-        match (reminders.remove(reminder), reminders.is_empty()) {
-            (false, false) => Err(format!(
-                "This reminder not present in Guild {}, Channel {}",
-                guild_id, channel_id
-            )
-            .into()),
-            (false, true) => {
-                // there should never be a case when there is an empty set in the hashmap
-                unreachable!();
+        let Some(position) = reminders.iter().position(|reminder| reminder.id == id) else {
+            return Err(format!("Reminder {} not present in {}", id, key).into());
+        };
+
+        let removed = reminders.remove(position);
+        // there should never be an empty list in the hashmap
+        if reminders.is_empty() {
+            match key {
+                ReminderKey::Channel(guild_id, channel_id) => {
+                    self.map.remove(&(guild_id, channel_id));
+                }
+                ReminderKey::User(user_id) => {
+                    self.personal.remove(&user_id);
+                }
             }
-            (true, false) => Ok(()),
-            (true, true) => {
-                self.map.remove(&(guild_id, channel_id));
-                Ok(())
+        }
+        Ok(removed)
+    }
+
+    /// Replace the stored reminder that has the same id.
+    /// Returns the reminder as stored.
+    pub fn update_reminder(
+        &mut self,
+        key: ReminderKey,
+        mut reminder: Reminder,
+    ) -> Result<Reminder, Error> {
+        self.apply_holidays(key, &mut reminder);
+        match self.get_reminder_mut(key, reminder.id) {
+            Some(stored) => {
+                *stored = reminder.clone();
+                Ok(reminder)
             }
+            None => Err(format!("Reminder {} not present in {}", reminder.id, key).into()),
+        }
+    }
+
+    pub fn get_reminders(&self, key: ReminderKey) -> Option<&[Reminder]> {
+        match key {
+            ReminderKey::Channel(guild_id, channel_id) => self.map.get(&(guild_id, channel_id)),
+            ReminderKey::User(user_id) => self.personal.get(&user_id),
         }
+        .map(Vec::as_slice)
+    }
+
+    /// Find a reminder in a guild/channel pair or a user's reminders by its id
+    pub fn get_reminder(&self, key: ReminderKey, id: ReminderId) -> Option<&Reminder> {
+        self.get_reminders(key)?
+            .iter()
+            .find(|reminder| reminder.id == id)
+    }
+
+    fn get_reminder_mut(&mut self, key: ReminderKey, id: ReminderId) -> Option<&mut Reminder> {
+        self.reminders_mut(key)?
+            .iter_mut()
+            .find(|reminder| reminder.id == id)
+    }
+
+    /// Iterator over every guild/channel pair and user, and the reminders set for it
+    pub fn iter(&self) -> impl Iterator<Item = (ReminderKey, &Vec<Reminder>)> {
+        self.map
+            .iter()
+            .map(|(&(guild_id, channel_id), reminders)| {
+                (ReminderKey::Channel(guild_id, channel_id), reminders)
+            })
+            .chain(
+                self.personal
+                    .iter()
+                    .map(|(&user_id, reminders)| (ReminderKey::User(user_id), reminders)),
+            )
     }
 
-    /// Mutable reference to HashSet for guild/channel pair.
-    /// Allows direct changes, but if the set becomes empty, it is not set to None in the map.
-    pub fn get_reminders_mut(
+    /// Subscribe a user to one reminder, or to every reminder in a channel if no id is given
+    pub fn subscribe(
         &mut self,
-        guild_id: GuildId,
-        channel_id: ChannelId,
-    ) -> Option<&mut HashSet<Reminder>> {
-        self.map.get_mut(&(guild_id, channel_id))
+        key: ReminderKey,
+        user_id: UserId,
+        id: Option<ReminderId>,
+    ) -> Result<(), Error> {
+        let ReminderKey::Channel(guild_id, channel_id) = key else {
+            return Err("Personal reminders are already sent by DM.".into());
+        };
+        let subscribers = match id {
+            Some(id) => match self.get_reminder_mut(key, id) {
+                Some(reminder) => &mut reminder.subscribers,
+                None => return Err(format!("Reminder {} not present in {}", id, key).into()),
+            },
+            None => self
+                .channel_subscribers
+                .entry((guild_id, channel_id))
+                .or_default(),
+        };
+        if subscribers.contains(&user_id) {
+            return Err("You are already subscribed.".into());
+        }
+        subscribers.push(user_id);
+        Ok(())
     }
 
-    pub fn get_reminders(
+    /// Undo `subscribe`
+    pub fn unsubscribe(
         &mut self,
-        guild_id: GuildId,
-        channel_id: ChannelId,
-    ) -> Option<&HashSet<Reminder>> {
-        self.map.get(&(guild_id, channel_id))
+        key: ReminderKey,
+        user_id: UserId,
+        id: Option<ReminderId>,
+    ) -> Result<(), Error> {
+        let ReminderKey::Channel(guild_id, channel_id) = key else {
+            return Err("Personal reminders are already sent by DM.".into());
+        };
+        let subscribers = match id {
+            Some(id) => self
+                .get_reminder_mut(key, id)
+                .map(|reminder| &mut reminder.subscribers),
+            None => self.channel_subscribers.get_mut(&(guild_id, channel_id)),
+        };
+        let Some(subscribers) = subscribers.filter(|subscribers| subscribers.contains(&user_id))
+        else {
+            return Err("You are not subscribed.".into());
+        };
+        subscribers.retain(|&subscriber| subscriber != user_id);
+        // there should never be an empty list in the hashmap
+        if self
+            .channel_subscribers
+            .get(&(guild_id, channel_id))
+            .is_some_and(Vec::is_empty)
+        {
+            self.channel_subscribers.remove(&(guild_id, channel_id));
+        }
+        Ok(())
+    }
+
+    /// Stops a user getting a personal reminder that someone else set for them.
+    /// Once nobody else is left, it goes to whoever set it.
+    pub fn stop_receiving(&mut self, user_id: UserId, id: ReminderId) -> Result<(), Error> {
+        let reminder = self
+            .personal
+            .values_mut()
+            .flatten()
+            .find(|reminder| reminder.id == id && reminder.recipients.contains(&user_id));
+        let Some(reminder) = reminder else {
+            return Err(format!("Reminder {} is not sent to you.", id).into());
+        };
+        reminder
+            .recipients
+            .retain(|&recipient| recipient != user_id);
+        Ok(())
+    }
+
+    /// Users sent a copy of a reminder, from either kind of subscription
+    pub fn subscribers(&self, key: ReminderKey, reminder: &Reminder) -> Vec<UserId> {
+        let ReminderKey::Channel(guild_id, channel_id) = key else {
+            return Vec::new();
+        };
+        let mut subscribers = self
+            .channel_subscribers
+            .get(&(guild_id, channel_id))
+            .cloned()
+            .unwrap_or_default();
+        for &user_id in &reminder.subscribers {
+            if !subscribers.contains(&user_id) {
+                subscribers.push(user_id);
+            }
+        }
+        subscribers
+    }
+
+    /// Every subscription of a user, and personal reminders other users set for them.
+    /// Subscriptions to a whole channel have no reminder.
+    pub fn subscriptions(&self, user_id: UserId) -> Vec<(ReminderKey, Option<&Reminder>)> {
+        let mut subscriptions = self
+            .channel_subscribers
+            .iter()
+            .filter(|(_, subscribers)| subscribers.contains(&user_id))
+            .map(|(&(guild_id, channel_id), _)| (ReminderKey::Channel(guild_id, channel_id), None))
+            .chain(self.iter().flat_map(|(key, reminders)| {
+                reminders
+                    .iter()
+                    .filter(move |reminder| match key {
+                        ReminderKey::Channel(..) => reminder.subscribers.contains(&user_id),
+                        ReminderKey::User(owner) => {
+                            owner != user_id && reminder.recipients.contains(&user_id)
+                        }
+                    })
+                    .map(move |reminder| (key, Some(reminder)))
+            }))
+            .collect::<Vec<_>>();
+        subscriptions.sort_by_key(|(key, reminder)| (*key, reminder.map(Reminder::id)));
+        subscriptions
+    }
+
+    /// Records that a user is done with the occurrence of a reminder due at `due`.
+    /// Returns everyone done with it so far. Occurrences long past are forgotten.
+    pub fn acknowledge(
+        &mut self,
+        id: ReminderId,
+        due: Timestamp,
+        user_id: UserId,
+    ) -> Result<&[UserId], Error> {
+        let oldest = due.unix_timestamp() - ACKNOWLEDGEMENT_DAYS * 86400;
+        self.acknowledgements.retain(|_, occurrences| {
+            occurrences.retain(|&due, _| due >= oldest);
+            !occurrences.is_empty()
+        });
+
+        let users = self
+            .acknowledgements
+            .entry(id)
+            .or_default()
+            .entry(due.unix_timestamp())
+            .or_default();
+        if users.contains(&user_id) {
+            return Err("You have already marked this reminder as done.".into());
+        }
+        users.push(user_id);
+        Ok(users)
+    }
+
+    /// Users done with each remembered occurrence of a reminder, by when it was due, oldest first
+    pub fn acknowledgements(&self, id: ReminderId) -> Vec<(Timestamp, &[UserId])> {
+        self.acknowledgements
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .filter_map(|(&due, users)| {
+                Some((Timestamp::from_unix_timestamp(due).ok()?, users.as_slice()))
+            })
+            .collect()
     }
 }
 
@@ -274,3 +1295,418 @@ impl Default for ReminderTable {
         ReminderTable::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::{at, one_off, START};
+
+    fn nth(interval: Interval, every: u32, n: u32) -> i64 {
+        let mut repeat = Repeat::new(interval, every).unwrap();
+        let start = at(START);
+        for _ in 0..n {
+            repeat.increment_index(&start);
+        }
+        repeat.next(&start).unix_timestamp()
+    }
+
+    #[test]
+    fn first_repeat_is_initial_timestamp() {
+        assert_eq!(nth(Interval::Hourly, 1, 0), START);
+        assert_eq!(nth(Interval::Monthly, 1, 0), START);
+    }
+
+    #[test]
+    fn minutely() {
+        assert_eq!(nth(Interval::Minutely, 5, 1), START + 300);
+        assert_eq!(nth(Interval::Minutely, 30, 3), START + 3 * 1800);
+    }
+
+    #[test]
+    fn hourly() {
+        assert_eq!(nth(Interval::Hourly, 1, 1), START + 3600);
+        assert_eq!(nth(Interval::Hourly, 6, 2), START + 12 * 3600);
+    }
+
+    #[test]
+    fn daily() {
+        assert_eq!(nth(Interval::Daily, 1, 1), START + 86400);
+        assert_eq!(nth(Interval::Daily, 1, 30), START + 30 * 86400);
+    }
+
+    #[test]
+    fn weekly() {
+        assert_eq!(nth(Interval::Weekly, 1, 2), START + 14 * 86400);
+    }
+
+    #[test]
+    fn monthly() {
+        // 2024-02-29 09:00:00, clamped to the end of February
+        assert_eq!(nth(Interval::Monthly, 1, 1), 1709197200);
+        // 2024-03-31 09:00:00
+        assert_eq!(nth(Interval::Monthly, 1, 2), 1711875600);
+    }
+
+    #[test]
+    fn yearly() {
+        // 2025-01-31 09:00:00
+        assert_eq!(nth(Interval::Yearly, 1, 1), 1738314000);
+    }
+
+    #[test]
+    fn every_n_units() {
+        assert_eq!(nth(Interval::Weekly, 2, 1), START + 14 * 86400);
+        assert_eq!(nth(Interval::Daily, 3, 2), START + 6 * 86400);
+        // 2024-07-31 09:00:00
+        assert_eq!(nth(Interval::Monthly, 6, 1), 1722416400);
+    }
+
+    #[test]
+    fn parse_repeat() {
+        let parse = |s: &str| s.parse::<Repeat>().map(|r| r.recurrence);
+        let every = |interval, every| Recurrence::Every { interval, every };
+        assert_eq!(parse("Weekly").unwrap(), every(Interval::Weekly, 1));
+        assert_eq!(parse("fortnightly").unwrap(), every(Interval::Weekly, 2));
+        assert_eq!(parse("every 3 days").unwrap(), every(Interval::Daily, 3));
+        assert_eq!(
+            parse("every 6 months").unwrap(),
+            every(Interval::Monthly, 6)
+        );
+        assert_eq!(parse("every hour").unwrap(), every(Interval::Hourly, 1));
+        assert!(matches!(parse("0 9 * * MON,WED"), Ok(Recurrence::Cron(_))));
+        assert!(matches!(
+            parse("RRULE:FREQ=WEEKLY;BYDAY=TU,TH;UNTIL=20240322"),
+            Ok(Recurrence::RRule(_))
+        ));
+        assert!(parse("* * * * *").is_err());
+        assert!(parse("every 2 minutes").is_err());
+        assert!(parse("every few days").is_err());
+        // five words that are not cron are read as an interval, not as a broken cron
+        assert!(parse("every 2 weeks on monday")
+            .unwrap_err()
+            .to_string()
+            .contains("Could not understand"));
+        assert!(parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn display_repeat() {
+        assert_eq!(
+            Repeat::new(Interval::Daily, 1).unwrap().to_string(),
+            "Daily"
+        );
+        assert_eq!(
+            Repeat::new(Interval::Weekly, 2).unwrap().to_string(),
+            "Every 2 weeks"
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let start = at(START);
+        let mut repeat = Repeat::new(Interval::Daily, 1).unwrap();
+        repeat.max_occurrences = Some(3);
+        assert_eq!(repeat.upcoming(&start, 5).len(), 3);
+
+        repeat.max_occurrences = None;
+        repeat.until = Some(at(START + 86400));
+        assert_eq!(repeat.upcoming(&start, 5).len(), 2);
+        repeat.record_sent();
+        repeat.increment_index(&start);
+        assert!(!repeat.is_finished(&start));
+        repeat.record_sent();
+        repeat.increment_index(&start);
+        assert!(repeat.is_finished(&start));
+    }
+
+    #[test]
+    fn skips_excluded_dates() {
+        let start = at(START);
+        let day = |n: i64| START + n * 86400;
+        let mut repeat = Repeat::new(Interval::Daily, 1).unwrap();
+        repeat.skipped = DateRange::parse_list("2024-02-01..2024-02-02, 2024-02-04").unwrap();
+        let upcoming = repeat
+            .upcoming(&start, 4)
+            .iter()
+            .map(Timestamp::unix_timestamp)
+            .collect::<Vec<_>>();
+        assert_eq!(upcoming, vec![day(0), day(3), day(5), day(6)]);
+
+        // the index moves past skipped repeats, so nothing is sent twice
+        repeat.increment_index(&start);
+        assert_eq!(repeat.next(&start).unix_timestamp(), day(3));
+        repeat.increment_index(&start);
+        assert_eq!(repeat.next(&start).unix_timestamp(), day(5));
+    }
+
+    #[test]
+    fn skips_guild_holidays() {
+        let guild_id = GuildId::new(1);
+        let key = ReminderKey::Channel(guild_id, ChannelId::new(2));
+        let start = at(START);
+        let mut table = ReminderTable::new();
+
+        let mut repeat = Repeat::new(Interval::Weekly, 1).unwrap();
+        repeat.skip_holidays = true;
+        let reminder = Reminder {
+            id: ReminderId::default(),
+            registered_at: start,
+            registered_by: UserId::new(3),
+            target_date: start,
+            repeating: Some(repeat),
+            name: None,
+            roles: None,
+            description: None,
+            missed: None,
+            lead_times: Vec::new(),
+            recipients: Vec::new(),
+            subscribers: Vec::new(),
+            thread: false,
+            deadline: None,
+        };
+        let id = table.add_reminder(key, reminder).unwrap().id();
+
+        let range = "2024-01-29..2024-02-04".parse().unwrap();
+        assert_eq!(
+            table
+                .set_holiday(guild_id, "Break".to_string(), range)
+                .len(),
+            1
+        );
+        let next = |table: &ReminderTable| {
+            let reminder = table.get_reminder(key, id).unwrap();
+            reminder
+                .repeating
+                .as_ref()
+                .unwrap()
+                .next(&start)
+                .unix_timestamp()
+        };
+        assert_eq!(next(&table), START + 7 * 86400);
+
+        table.remove_holiday(guild_id, "Break").unwrap();
+        assert_eq!(next(&table), START);
+        assert!(table.remove_holiday(guild_id, "Break").is_err());
+    }
+
+    #[test]
+    fn personal_reminders() {
+        let user_id = UserId::new(3);
+        let key = ReminderKey::User(user_id);
+        let start = at(START);
+        let mut table = ReminderTable::new();
+
+        let mut repeat = Repeat::new(Interval::Weekly, 1).unwrap();
+        repeat.skip_holidays = true;
+        let mut reminder = one_off(user_id, start);
+        reminder.repeating = Some(repeat);
+        let id = table.add_reminder(key, reminder).unwrap().id();
+
+        // holidays belong to guilds, so do not apply to personal reminders
+        let range = "2024-01-29..2024-02-04".parse().unwrap();
+        assert!(table
+            .set_holiday(GuildId::new(1), "Break".to_string(), range)
+            .is_empty());
+        let reminder = table.get_reminder(key, id).unwrap();
+        assert_eq!(reminder.recipients(), vec![user_id]);
+        assert!(table
+            .get_reminder(ReminderKey::Channel(GuildId::new(1), ChannelId::new(2)), id)
+            .is_none());
+        assert_eq!(
+            table.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            vec![key]
+        );
+
+        table.remove_reminder(key, id).unwrap();
+        assert!(table.get_reminders(key).is_none());
+        assert!(table.remove_reminder(key, id).is_err());
+    }
+
+    #[test]
+    fn subscriptions() {
+        let key = ReminderKey::Channel(GuildId::new(1), ChannelId::new(2));
+        let (alice, bob) = (UserId::new(3), UserId::new(4));
+        let start = at(START);
+        let mut table = ReminderTable::new();
+
+        let reminder = one_off(alice, start);
+        let id = table.add_reminder(key, reminder).unwrap().id();
+        table.subscribe(key, alice, None).unwrap();
+        table.subscribe(key, alice, Some(id)).unwrap();
+        table.subscribe(key, bob, Some(id)).unwrap();
+        assert!(table.subscribe(key, bob, Some(id)).is_err());
+        assert!(table.subscribe(key, bob, Some(ReminderId(99))).is_err());
+        assert!(table.subscribe(ReminderKey::User(bob), bob, None).is_err());
+
+        // each subscriber only gets one copy
+        let reminder = table.get_reminder(key, id).unwrap();
+        assert_eq!(table.subscribers(key, reminder), vec![alice, bob]);
+        assert_eq!(table.subscriptions(alice).len(), 2);
+
+        table.unsubscribe(key, alice, None).unwrap();
+        assert!(table.unsubscribe(key, alice, None).is_err());
+        table.unsubscribe(key, bob, Some(id)).unwrap();
+        let reminder = table.get_reminder(key, id).unwrap();
+        assert_eq!(table.subscribers(key, reminder), vec![alice]);
+        assert!(table.subscriptions(bob).is_empty());
+    }
+
+    #[test]
+    fn settings_read_back_without_reminders() {
+        let guild_id = GuildId::new(1);
+        let key = ReminderKey::Channel(guild_id, ChannelId::new(2));
+        let start = at(START);
+        let mut table = ReminderTable::new();
+        let range = "2024-02-19..2024-02-23".parse().unwrap();
+        table.set_holiday(guild_id, "Break".to_string(), range);
+        let reminder = one_off(UserId::new(3), start);
+        let id = table.add_reminder(key, reminder).unwrap().id();
+        table.subscribe(key, UserId::new(4), None).unwrap();
+
+        let bytes = serde_cbor::to_vec(&table.settings()).unwrap();
+        let read: ReminderTable = serde_cbor::from_slice(&bytes).unwrap();
+        table.remove_reminder(key, id).unwrap();
+        assert_eq!(read, table);
+    }
+
+    #[test]
+    fn acknowledgements() {
+        let id = ReminderId(1);
+        let (alice, bob) = (UserId::new(3), UserId::new(4));
+        let mut table = ReminderTable::new();
+
+        assert_eq!(table.acknowledge(id, at(START), alice).unwrap(), [alice]);
+        assert_eq!(table.acknowledge(id, at(START), bob).unwrap(), [alice, bob]);
+        assert!(table.acknowledge(id, at(START), bob).is_err());
+        // each occurrence is kept apart
+        let next_week = START + 7 * 86400;
+        assert_eq!(table.acknowledge(id, at(next_week), bob).unwrap(), [bob]);
+        assert_eq!(
+            table.acknowledgements(id),
+            vec![(at(START), &[alice, bob][..]), (at(next_week), &[bob][..])]
+        );
+        assert!(table.acknowledgements(ReminderId(2)).is_empty());
+
+        // old ones are forgotten
+        let later = next_week + (ACKNOWLEDGEMENT_DAYS + 1) * 86400;
+        table.acknowledge(ReminderId(2), at(later), alice).unwrap();
+        assert!(table.acknowledgements(id).is_empty());
+    }
+
+    #[test]
+    fn stop_receiving() {
+        let (alice, bob) = (UserId::new(3), UserId::new(4));
+        let key = ReminderKey::User(alice);
+        let start = at(START);
+        let mut table = ReminderTable::new();
+
+        let mut reminder = one_off(alice, start);
+        reminder.recipients = vec![bob];
+        let id = table.add_reminder(key, reminder).unwrap().id();
+        // listed for bob, but not for alice who set it
+        assert_eq!(table.subscriptions(bob).len(), 1);
+        assert!(table.subscriptions(alice).is_empty());
+
+        assert!(table.stop_receiving(alice, id).is_err());
+        table.stop_receiving(bob, id).unwrap();
+        assert!(table.subscriptions(bob).is_empty());
+        assert!(table.stop_receiving(bob, id).is_err());
+        // nobody else is left, so it goes back to alice
+        let reminder = table.get_reminder(key, id).unwrap();
+        assert_eq!(reminder.recipients(), vec![alice]);
+    }
+
+    #[test]
+    fn parse_date_ranges() {
+        assert!("2024-02-23..2024-02-19".parse::<DateRange>().is_err());
+        assert!("next week".parse::<DateRange>().is_err());
+        assert_eq!(
+            "2024-02-19 .. 2024-02-23"
+                .parse::<DateRange>()
+                .unwrap()
+                .to_string(),
+            "2024-02-19 to 2024-02-23"
+        );
+    }
+
+    #[test]
+    fn keeps_local_time_across_dst() {
+        // Friday 2024-03-29 09:00 in London (GMT), clocks go forward on Sunday
+        let start = at(1711702800);
+        let mut repeat = Repeat::new(Interval::Daily, 1).unwrap();
+        repeat.time_zone = Some(chrono_tz::Europe::London);
+        let upcoming = repeat
+            .upcoming(&start, 4)
+            .iter()
+            .map(Timestamp::unix_timestamp)
+            .collect::<Vec<_>>();
+        // 09:00 BST is 08:00 UTC
+        assert_eq!(
+            upcoming,
+            vec![1711702800, 1711789200, 1711872000, 1711958400]
+        );
+
+        // 01:30 does not exist on the day the clocks go forward, so it is sent at 02:30 BST
+        let mut repeat = Repeat::cron("30 1 * * *".parse().unwrap());
+        repeat.time_zone = Some(chrono_tz::Europe::London);
+        let upcoming = repeat.upcoming(&start, 3);
+        assert_eq!(upcoming[1].unix_timestamp(), 1711848600);
+        // 2024-04-01 01:30 BST
+        assert_eq!(upcoming[2].unix_timestamp(), 1711931400);
+    }
+
+    #[test]
+    fn lead_times_within_repeats() {
+        let start = at(START);
+        let mut reminder = one_off(UserId::new(1), start);
+        reminder.lead_times = vec![120];
+        // one-off reminders can be reminded about any time before
+        assert!(reminder.check_lead_times().is_ok());
+
+        reminder.repeating = Some(Repeat::new(Interval::Hourly, 1).unwrap());
+        assert!(reminder.check_lead_times().is_err());
+        reminder.lead_times = vec![59, 30];
+        assert!(reminder.check_lead_times().is_ok());
+
+        // cron repeats use the closest two, here Monday to Wednesday
+        reminder.repeating = Some("0 9 * * MON,WED".parse().unwrap());
+        reminder.lead_times = vec![3 * 1440];
+        assert!(reminder.check_lead_times().is_err());
+        reminder.lead_times = vec![1440];
+        assert!(reminder.check_lead_times().is_ok());
+    }
+
+    #[test]
+    fn rejects_short_or_empty_repeats() {
+        assert!(Repeat::new(Interval::Minutely, 1).is_err());
+        assert!(Repeat::new(Interval::Minutely, MIN_REPEAT_MINUTES).is_ok());
+        assert!(Repeat::new(Interval::Hourly, 0).is_err());
+    }
+
+    #[test]
+    fn rejects_repeats_never_sent() {
+        let start = at(START);
+        let never = "0 9 30 2 *".parse::<Repeat>().unwrap();
+        assert!(never.upcoming(&start, 5).is_empty());
+        assert!(never.check_sent(&start).is_err());
+        let ended = "FREQ=WEEKLY;UNTIL=20240101T000000Z"
+            .parse::<Repeat>()
+            .unwrap();
+        assert!(ended.check_sent(&start).is_err());
+
+        // the first time is the first match, not the reminder's timestamp
+        let mondays = "0 9 * * MON".parse::<Repeat>().unwrap();
+        assert!(mondays.check_sent(&start).is_ok());
+        assert_eq!(mondays.upcoming(&start, 1), vec![at(START + 5 * 86400)]);
+    }
+
+    #[test]
+    fn skip_past_moves_after_now() {
+        let start = at(START);
+        let now = at(START + 5 * 3600 + 1);
+        let mut repeat = Repeat::new(Interval::Hourly, 1).unwrap();
+        assert!(repeat.skip_past(&start, &now));
+        assert_eq!(repeat.next(&start).unix_timestamp(), START + 6 * 3600);
+    }
+}
@@ -0,0 +1,343 @@
+use chrono::{
+    DateTime, Datelike, Days, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+    Weekday,
+};
+
+use crate::{backend::data::Interval, Error};
+
+/// Time used for days given without one, e.g. "tomorrow" or "T1 W7 Fri"
+pub(crate) const DEFAULT_TIME: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
+    Some(time) => time,
+    None => NaiveTime::MIN,
+};
+
+/// Formats without an offset, read in the time zone of `now`
+const LOCAL_FORMATS: [&str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a date and time typed by a user.
+///
+/// Accepts ISO 8601 (`2024-03-01T12:00:00Z`, `2024-03-01T12:00`), `YYYY-MM-DD HH:MM`,
+/// a plain date (midnight), and relative forms such as "in 3 days", "in 2 hours 30 minutes",
+/// "tomorrow 9am", "friday" or "next friday 17:00".
+/// Days given without a time are at 9am.
+pub(crate) fn parse<Tz: TimeZone>(input: &str, now: &DateTime<Tz>) -> Result<DateTime<Tz>, Error> {
+    let input = input.trim();
+    let zone = now.timezone();
+
+    // an explicit offset is an exact point in time
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Ok(datetime.with_timezone(&zone));
+    }
+
+    let local = parse_local(input, now.naive_local())?;
+    from_local(&zone, local)
+}
+
+/// Converts a local date and time, taking the earlier one if the clocks go back
+pub(crate) fn from_local<Tz: TimeZone>(
+    zone: &Tz,
+    local: NaiveDateTime,
+) -> Result<DateTime<Tz>, Error> {
+    zone.from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in this time zone.", local).into())
+}
+
+fn parse_local(input: &str, now: NaiveDateTime) -> Result<NaiveDateTime, Error> {
+    for format in LOCAL_FORMATS {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(input, format) {
+            return Ok(datetime);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_time(NaiveTime::MIN));
+    }
+
+    let invalid = || -> Error {
+        format!(
+            "Could not understand \"{}\", try something like \"2024-03-01 12:00\", \
+            \"tomorrow 9am\" or \"in 3 days\".",
+            input
+        )
+        .into()
+    };
+
+    let lower = input.to_lowercase();
+    let words = lower
+        .split_whitespace()
+        .filter(|word| !matches!(*word, "at" | "on" | "and"))
+        .collect::<Vec<_>>();
+
+    match words.as_slice() {
+        ["in", rest @ ..] => relative(rest, now).ok_or_else(invalid),
+        words => day_and_time(words, now).ok_or_else(invalid),
+    }
+}
+
+/// "in 3 days", "in an hour", "in 1 week 2 days"
+fn relative(words: &[&str], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let pairs = words.chunks_exact(2);
+    if words.is_empty() || !pairs.remainder().is_empty() {
+        return None;
+    }
+
+    let mut datetime = now;
+    for pair in pairs {
+        let amount = match pair[0] {
+            "a" | "an" => 1,
+            n => n.parse::<u32>().ok()?,
+        };
+        datetime = match Interval::from_unit(pair[1])? {
+            Interval::Minutely => datetime.checked_add_signed(Duration::minutes(amount as i64))?,
+            Interval::Hourly => datetime.checked_add_signed(Duration::hours(amount as i64))?,
+            Interval::Daily => datetime.checked_add_days(Days::new(amount as u64))?,
+            Interval::Weekly => datetime.checked_add_days(Days::new(7 * amount as u64))?,
+            Interval::Monthly => datetime.checked_add_months(Months::new(amount))?,
+            Interval::Yearly => datetime.checked_add_months(Months::new(12 * amount))?,
+        };
+    }
+    Some(datetime)
+}
+
+/// "tomorrow 9am", "next friday 17:00", "today noon", or just a time
+fn day_and_time(words: &[&str], now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let today = now.date();
+    let mut date = None;
+    let mut time = None;
+    let mut words = words.iter();
+
+    while let Some(&word) = words.next() {
+        let day = match word {
+            "today" => Some(today),
+            "tomorrow" => today.succ_opt(),
+            // "next friday" is the same as "friday", the first one after today
+            "next" => match words.next()?.parse::<Weekday>() {
+                Ok(weekday) => Some(next_weekday(today, weekday)?),
+                Err(_) => return None,
+            },
+            _ => match word.parse::<Weekday>() {
+                Ok(weekday) => Some(next_weekday(today, weekday)?),
+                Err(_) => None,
+            },
+        };
+
+        match day {
+            Some(day) if date.is_none() => date = Some(day),
+            Some(_) => return None,
+            None if time.is_none() => time = Some(parse_time(word)?),
+            None => return None,
+        }
+    }
+
+    match (date, time) {
+        (Some(date), time) => Some(date.and_time(time.unwrap_or(DEFAULT_TIME))),
+        // a time on its own is the next time it comes around
+        (None, Some(time)) => {
+            let datetime = today.and_time(time);
+            match datetime > now {
+                true => Some(datetime),
+                false => datetime.checked_add_days(Days::new(1)),
+            }
+        }
+        (None, None) => None,
+    }
+}
+
+/// First `weekday` after `today`
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> Option<NaiveDate> {
+    let days = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+    let days = if days == 0 { 7 } else { days };
+    today.checked_add_days(Days::new(days as u64))
+}
+
+/// "17:00", "9am", "9:30pm", "noon" or "midnight"
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    match word {
+        "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return Some(NaiveTime::MIN),
+        _ => {}
+    }
+
+    let (clock, pm) = match (word.strip_suffix("am"), word.strip_suffix("pm")) {
+        (Some(clock), _) => (clock, Some(false)),
+        (_, Some(clock)) => (clock, Some(true)),
+        _ => (word, None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse().ok()?, minute.parse().ok()?),
+        Some(_) => return None,
+        // a bare number is only a time with am or pm after it
+        None if pm.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match pm {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(pm) => hour % 12 + if pm { 12 } else { 0 },
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Parses a length of time such as "1w", "2 days" or "1h 30m", in minutes
+pub(crate) fn parse_minutes(input: &str) -> Result<u32, Error> {
+    let invalid = || -> Error {
+        format!(
+            "Could not understand \"{}\", try something like \"1d\" or \"2 hours\".",
+            input.trim()
+        )
+        .into()
+    };
+
+    let mut total: u32 = 0;
+    let mut rest = input.trim().to_lowercase();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let amount = rest[..digits].parse::<u32>().map_err(|_| invalid())?;
+        let after = rest[digits..].trim_start();
+        let letters = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+
+        let unit = match &after[..letters] {
+            "m" => 1,
+            "h" => 60,
+            "d" => 24 * 60,
+            "w" => 7 * 24 * 60,
+            word => match Interval::from_unit(word).ok_or_else(invalid)? {
+                Interval::Minutely => 1,
+                Interval::Hourly => 60,
+                Interval::Daily => 24 * 60,
+                Interval::Weekly => 7 * 24 * 60,
+                // months and years are not a fixed length
+                _ => return Err(invalid()),
+            },
+        };
+        total = amount
+            .checked_mul(unit)
+            .and_then(|minutes| total.checked_add(minutes))
+            .ok_or_else(invalid)?;
+        rest = after[letters..]
+            .trim_start_matches(|c: char| c.is_whitespace() || c == ',')
+            .to_string();
+    }
+    Ok(total)
+}
+
+/// Formats a number of minutes such as "1 week" or "1 day 12 hours"
+pub(crate) fn format_minutes(minutes: u32) -> String {
+    let units = [
+        ("week", 7 * 24 * 60),
+        ("day", 24 * 60),
+        ("hour", 60),
+        ("minute", 1),
+    ];
+    let mut rest = minutes;
+    let mut parts = Vec::new();
+    for (unit, length) in units {
+        let amount = rest / length;
+        rest %= length;
+        match amount {
+            0 => {}
+            1 => parts.push(format!("1 {}", unit)),
+            n => parts.push(format!("{} {}s", n, unit)),
+        }
+    }
+    match parts.is_empty() {
+        true => "0 minutes".to_string(),
+        false => parts.join(" "),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn parse_at(input: &str) -> Option<String> {
+        // Wednesday 2024-01-31 10:30:00 UTC
+        let now = Utc.timestamp_opt(1706697000, 0).unwrap();
+        parse(input, &now)
+            .ok()
+            .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(
+            parse_at("2024-03-01T12:00:00Z").unwrap(),
+            "2024-03-01 12:00"
+        );
+        assert_eq!(
+            parse_at("2024-03-01T12:00:00+02:00").unwrap(),
+            "2024-03-01 10:00"
+        );
+        assert_eq!(parse_at("2024-03-01T12:00").unwrap(), "2024-03-01 12:00");
+        assert_eq!(parse_at("2024-03-01 12:00").unwrap(), "2024-03-01 12:00");
+        assert_eq!(parse_at("2024-03-01").unwrap(), "2024-03-01 00:00");
+    }
+
+    #[test]
+    fn local_time_zone() {
+        // 2024-03-30 12:00 UTC, the day before clocks go forward in the UK
+        let now = Utc
+            .timestamp_opt(1711800000, 0)
+            .unwrap()
+            .with_timezone(&chrono_tz::Europe::London);
+        let parsed = |input| parse(input, &now).unwrap().naive_utc().to_string();
+        assert_eq!(parsed("2024-03-30 09:00"), "2024-03-30 09:00:00");
+        assert_eq!(parsed("tomorrow 9am"), "2024-03-31 08:00:00");
+        assert!(parse("2024-03-31 01:30", &now).is_err());
+    }
+
+    #[test]
+    fn relative_dates() {
+        assert_eq!(parse_at("in 3 days").unwrap(), "2024-02-03 10:30");
+        assert_eq!(parse_at("in an hour").unwrap(), "2024-01-31 11:30");
+        assert_eq!(
+            parse_at("in 1 week and 2 days").unwrap(),
+            "2024-02-09 10:30"
+        );
+        assert_eq!(parse_at("in 1 month").unwrap(), "2024-02-29 10:30");
+        assert!(parse_at("in 3").is_none());
+        assert!(parse_at("in 3 fortnights").is_none());
+    }
+
+    #[test]
+    fn lengths_of_time() {
+        assert_eq!(parse_minutes("1w").unwrap(), 7 * 24 * 60);
+        assert_eq!(parse_minutes("2 days").unwrap(), 2 * 24 * 60);
+        assert_eq!(parse_minutes("1h 30m").unwrap(), 90);
+        assert_eq!(parse_minutes("1 hour 30 minutes").unwrap(), 90);
+        assert!(parse_minutes("1 month").is_err());
+        assert!(parse_minutes("soon").is_err());
+        assert!(parse_minutes("").is_err());
+        assert_eq!(format_minutes(7 * 24 * 60), "1 week");
+        assert_eq!(format_minutes(36 * 60), "1 day 12 hours");
+    }
+
+    #[test]
+    fn named_days() {
+        assert_eq!(parse_at("tomorrow 9am").unwrap(), "2024-02-01 09:00");
+        assert_eq!(parse_at("tomorrow").unwrap(), "2024-02-01 09:00");
+        assert_eq!(parse_at("today at 5:30pm").unwrap(), "2024-01-31 17:30");
+        assert_eq!(parse_at("next friday 17:00").unwrap(), "2024-02-02 17:00");
+        assert_eq!(parse_at("Wednesday noon").unwrap(), "2024-02-07 12:00");
+        assert_eq!(parse_at("9am").unwrap(), "2024-02-01 09:00");
+        assert_eq!(parse_at("12am").unwrap(), "2024-02-01 00:00");
+        assert!(parse_at("13pm").is_none());
+        assert!(parse_at("tomorrow friday").is_none());
+        assert!(parse_at("next week").is_none());
+        assert!(parse_at("soon").is_none());
+    }
+}
@@ -0,0 +1,474 @@
+use chrono_tz::Tz;
+use poise::serenity_prelude::{ChannelId, GuildId, RoleId, Timestamp, UserId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::data::{DateRange, MissedPolicy, Reminder, ReminderKey, ReminderTable, Repeat},
+    Error,
+};
+
+/// Version of the export format, so files from later releases can be told apart
+const EXPORT_VERSION: u32 = 1;
+
+/// Largest file `/reminder import` will read
+pub(crate) const MAX_IMPORT_BYTES: u32 = 1024 * 1024;
+
+/// Most reminders one import can add
+const MAX_IMPORT_REMINDERS: usize = 500;
+
+/// File formats a guild's reminders can be exported as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub(crate) enum ExportFormat {
+    #[default]
+    #[name = "JSON"]
+    Json,
+    #[name = "YAML"]
+    Yaml,
+}
+
+impl ExportFormat {
+    /// Guesses the format of an attachment from its file name, JSON unless it ends in .yaml/.yml
+    pub fn from_file_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if name.ends_with(".yaml") || name.ends_with(".yml") {
+            ExportFormat::Yaml
+        } else {
+            ExportFormat::Json
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Yaml => "yaml",
+        }
+    }
+}
+
+/// A guild's channel reminders, written so they can be read and edited by hand.
+/// Repeats and early reminders are given the same way as to `/reminder add`.
+/// Subscriptions are left out, both to reminders and to channels,
+/// so getting copies by DM is up to each user in the server the reminders are imported into.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GuildExport {
+    pub version: u32,
+    /// Guild the reminders were exported from
+    pub guild: GuildId,
+    pub reminders: Vec<ExportedReminder>,
+}
+
+/// One reminder in an export. Ids are left out, so importing adds it as a new reminder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ExportedReminder {
+    pub channel: ChannelId,
+    /// When it is next due
+    pub datetime: Timestamp,
+    /// When a repeat started, if before `datetime`.
+    /// Repeats are counted from here, so limits in RRULEs and days clamped to the end of a month
+    /// come out the same as before.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<Timestamp>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<Timestamp>,
+    /// How many more times a repeat is sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub times: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip: Vec<DateRange>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub holidays: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_zone: Option<Tz>,
+    /// Early reminders, e.g. "1d, 1h"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<RoleId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missed: Option<MissedPolicy>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub thread: bool,
+    /// Who set it, for reference. Imported reminders are set by the user importing them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<UserId>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl GuildExport {
+    /// Every channel reminder in a guild. Personal reminders belong to users, so are left out.
+    pub fn from_table(table: &ReminderTable, guild_id: GuildId) -> Self {
+        let reminders = table
+            .iter()
+            .filter_map(|(key, reminders)| match key {
+                ReminderKey::Channel(id, channel_id) if id == guild_id => {
+                    Some(reminders.iter().map(move |reminder| (channel_id, reminder)))
+                }
+                _ => None,
+            })
+            .flatten()
+            .map(|(channel_id, reminder)| ExportedReminder::from_reminder(channel_id, reminder))
+            .collect();
+        Self {
+            version: EXPORT_VERSION,
+            guild: guild_id,
+            reminders,
+        }
+    }
+
+    pub fn to_string(&self, format: ExportFormat) -> Result<String, Error> {
+        Ok(match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+            ExportFormat::Yaml => serde_yaml::to_string(self)?,
+        })
+    }
+
+    pub fn parse(input: &[u8], format: ExportFormat) -> Result<Self, Error> {
+        let export: Self = match format {
+            ExportFormat::Json => serde_json::from_slice(input)
+                .map_err(|e| format!("The file is not a valid JSON export: {}", e))?,
+            ExportFormat::Yaml => serde_yaml::from_slice(input)
+                .map_err(|e| format!("The file is not a valid YAML export: {}", e))?,
+        };
+        if export.version > EXPORT_VERSION {
+            return Err(format!(
+                "The file is export version {}, but only up to {} is supported.",
+                export.version, EXPORT_VERSION
+            )
+            .into());
+        }
+        if export.reminders.len() > MAX_IMPORT_REMINDERS {
+            return Err(format!(
+                "At most {} reminders can be imported at once.",
+                MAX_IMPORT_REMINDERS
+            )
+            .into());
+        }
+        Ok(export)
+    }
+}
+
+impl ExportedReminder {
+    pub fn from_reminder(channel_id: ChannelId, reminder: &Reminder) -> Self {
+        let repeat = reminder.repeating.as_ref();
+        let datetime = reminder.next_due();
+        Self {
+            channel: channel_id,
+            datetime,
+            start: Some(reminder.target_date)
+                .filter(|start| repeat.is_some() && *start != datetime),
+            repeat: repeat.map(Repeat::to_input),
+            until: repeat.and_then(|repeat| repeat.until),
+            times: repeat.and_then(|repeat| {
+                repeat
+                    .max_occurrences
+                    .map(|max| max.saturating_sub(repeat.sent()))
+            }),
+            skip: repeat
+                .map(|repeat| repeat.skipped.clone())
+                .unwrap_or_default(),
+            holidays: repeat.is_some_and(|repeat| repeat.skip_holidays),
+            time_zone: repeat.and_then(|repeat| repeat.time_zone),
+            before: Some(&reminder.lead_times)
+                .filter(|lead_times| !lead_times.is_empty())
+                .map(|lead_times| {
+                    lead_times
+                        .iter()
+                        .map(|minutes| format!("{}m", minutes))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }),
+            name: reminder.name.clone(),
+            text: reminder.description.clone(),
+            roles: reminder.roles.clone().unwrap_or_default(),
+            missed: reminder.missed,
+            thread: reminder.thread,
+            created_by: Some(reminder.get_creation().1),
+        }
+    }
+
+    /// Checks the entry the same way `/reminder add` checks its options,
+    /// and builds the reminder as set by `user_id`.
+    /// Repeats pick up from `datetime`, or their next time after `now` if that has passed.
+    pub fn to_reminder(&self, user_id: UserId, now: Timestamp) -> Result<Reminder, Error> {
+        // maximum character count in a embed description is 4096
+        if self.text.as_ref().is_some_and(|s| s.chars().count() > 4096) {
+            return Err("The reminder text must be at most 4096 characters long.".into());
+        }
+
+        let start = self.start.unwrap_or(self.datetime);
+        if start > self.datetime {
+            return Err("The start of a repeat must not be after its timestamp.".into());
+        }
+        let repeating = match &self.repeat {
+            Some(repeat) => {
+                let mut repeat = repeat.parse::<Repeat>()?;
+                repeat.time_zone = self.time_zone;
+                if self.times == Some(0) {
+                    return Err("A repeat must be sent at least once.".into());
+                }
+                repeat.max_occurrences = self.times;
+                repeat.skipped = self
+                    .skip
+                    .iter()
+                    .map(|range| DateRange::new(range.start, range.end))
+                    .collect::<Result<_, _>>()?;
+                repeat.skip_holidays = self.holidays;
+                if self.until.is_some_and(|until| until <= self.datetime) {
+                    return Err("The end of the repeat must be after its timestamp.".into());
+                }
+                repeat.until = self.until;
+                // the times before `datetime` were sent before the export
+                let before = Timestamp::from_unix_timestamp(self.datetime.unix_timestamp() - 1)?;
+                if !repeat.skip_past(&start, &before)
+                    || !repeat.skip_past(&start, &now)
+                    || repeat.is_finished(&start)
+                {
+                    return Err("The repeat has no times left after now.".into());
+                }
+                repeat.check_sent(&start)?;
+                Some(repeat)
+            }
+            None if self.start.is_some()
+                || self.until.is_some()
+                || self.times.is_some()
+                || !self.skip.is_empty()
+                || self.holidays =>
+            {
+                return Err(
+                    "Starts, end dates, limits and skipped days can only be set for repeating reminders."
+                        .into(),
+                );
+            }
+            None if self.datetime <= now => {
+                return Err("The timestamp must be in the future.".into());
+            }
+            None => None,
+        };
+
+        let mut reminder = Reminder::one_off(
+            user_id,
+            start,
+            self.name.clone(),
+            Some(self.roles.clone()),
+            self.text.clone(),
+        );
+        reminder.repeating = repeating;
+        reminder.lead_times = match &self.before {
+            Some(before) => Reminder::parse_lead_times(before)?,
+            None => Vec::new(),
+        };
+        reminder.check_lead_times()?;
+        reminder.missed = self.missed;
+        reminder.thread = self.thread;
+        Ok(reminder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        backend::data::{Interval, Recurrence},
+        test_util::{at, one_off, START},
+    };
+
+    fn table() -> (ReminderTable, GuildId) {
+        let guild_id = GuildId::new(1);
+        let channel = ReminderKey::Channel(guild_id, ChannelId::new(2));
+        let other = ReminderKey::Channel(GuildId::new(9), ChannelId::new(9));
+        let personal = ReminderKey::User(UserId::new(3));
+
+        let mut table = ReminderTable::new();
+        let mut weekly = Reminder::one_off(
+            UserId::new(3),
+            at(START),
+            Some("Lab report".to_string()),
+            Some(vec![RoleId::new(4)]),
+            Some("Due on Friday".to_string()),
+        );
+        let mut repeat = "every 2 weeks".parse::<Repeat>().unwrap();
+        repeat.max_occurrences = Some(5);
+        repeat.time_zone = Some(chrono_tz::Europe::London);
+        repeat.skipped = DateRange::parse_list("2024-02-19..2024-02-23").unwrap();
+        repeat.record_sent();
+        repeat.increment_index(&at(START));
+        weekly.repeating = Some(repeat);
+        weekly.lead_times = vec![1440, 60];
+        weekly.thread = true;
+        table.add_reminder(channel, weekly).unwrap();
+
+        let mut cron = one_off(UserId::new(5), at(START));
+        cron.repeating = Some("0 9 * * MON,WED".parse().unwrap());
+        table.add_reminder(channel, cron).unwrap();
+
+        let once = one_off(UserId::new(3), at(START + 3600));
+        table.add_reminder(other, once.clone()).unwrap();
+        table.add_reminder(personal, once).unwrap();
+        (table, guild_id)
+    }
+
+    #[test]
+    fn exports_guild_reminders() {
+        let (table, guild_id) = table();
+        let export = GuildExport::from_table(&table, guild_id);
+        // only the two in the guild's channel
+        assert_eq!(export.reminders.len(), 2);
+
+        let lab = export
+            .reminders
+            .iter()
+            .find(|reminder| reminder.name.is_some())
+            .unwrap();
+        assert_eq!(lab.datetime, at(START + 14 * 86400));
+        assert_eq!(lab.start, Some(at(START)));
+        assert_eq!(lab.repeat.as_deref(), Some("every 2 weeks"));
+        assert_eq!(lab.times, Some(4));
+        assert_eq!(lab.before.as_deref(), Some("1440m, 60m"));
+
+        for format in [ExportFormat::Json, ExportFormat::Yaml] {
+            let text = export.to_string(format).unwrap();
+            assert_eq!(GuildExport::parse(text.as_bytes(), format).unwrap(), export);
+        }
+    }
+
+    #[test]
+    fn imports_what_was_exported() {
+        let (table, guild_id) = table();
+        let export = GuildExport::from_table(&table, guild_id);
+        let now = at(START - 3600);
+        let importer = UserId::new(6);
+        for (exported, original) in export.reminders.iter().zip(
+            table
+                .get_reminders(ReminderKey::Channel(guild_id, ChannelId::new(2)))
+                .unwrap(),
+        ) {
+            let imported = exported.to_reminder(importer, now).unwrap();
+            assert_eq!(imported.next_due(), original.next_due());
+            assert_eq!(imported.get_creation().1, importer);
+            assert_eq!(imported.roles, original.roles);
+            assert_eq!(imported.lead_times, original.lead_times);
+            assert_eq!(imported.thread, original.thread);
+            let (imported, original) = (
+                imported.repeating.unwrap(),
+                original.repeating.clone().unwrap(),
+            );
+            assert_eq!(imported.recurrence, original.recurrence);
+            assert_eq!(imported.skipped, original.skipped);
+            assert_eq!(imported.time_zone, original.time_zone);
+        }
+    }
+
+    #[test]
+    fn repeats_carry_on_after_now() {
+        let (table, guild_id) = table();
+        let mut export = GuildExport::from_table(&table, guild_id);
+        let lab = &mut export.reminders[0];
+        lab.skip.clear();
+        lab.times = None;
+        lab.repeat = Some("weekly".to_string());
+        let imported = lab
+            .to_reminder(UserId::new(6), at(lab.datetime.unix_timestamp() + 1))
+            .unwrap();
+        assert_eq!(
+            imported.repeating.as_ref().unwrap().recurrence,
+            Recurrence::Every {
+                interval: Interval::Weekly,
+                every: 1
+            }
+        );
+        assert_eq!(
+            imported.next_due(),
+            at(lab.datetime.unix_timestamp() + 7 * 86400)
+        );
+    }
+
+    /// Imports a repeat exported after it was sent `sent` times
+    fn reimport(repeat: &str, start: i64, sent: u32) -> Reminder {
+        let mut reminder = one_off(UserId::new(3), at(start));
+        let mut repeat = repeat.parse::<Repeat>().unwrap();
+        for _ in 0..sent {
+            repeat.record_sent();
+            repeat.increment_index(&at(start));
+        }
+        reminder.repeating = Some(repeat);
+        let exported = ExportedReminder::from_reminder(ChannelId::new(2), &reminder);
+        exported.to_reminder(UserId::new(6), at(start)).unwrap()
+    }
+
+    #[test]
+    fn repeats_keep_their_progress() {
+        // 2024-01-31, so later months are clamped to their last day
+        let mut monthly = reimport("monthly", START, 1);
+        let repeat = monthly.repeating.as_mut().unwrap();
+        assert_eq!(repeat.next(&monthly.target_date), at(1709197200)); // 2024-02-29
+        repeat.increment_index(&monthly.target_date);
+        assert_eq!(repeat.next(&monthly.target_date), at(1711875600)); // 2024-03-31
+
+        // only the third of three is left
+        let mut counted = reimport("FREQ=WEEKLY;COUNT=3", START, 2);
+        let repeat = counted.repeating.as_mut().unwrap();
+        assert_eq!(repeat.next(&counted.target_date), at(START + 14 * 86400));
+        repeat.increment_index(&counted.target_date);
+        // does not move forward once the count is used up
+        assert_eq!(repeat.next(&counted.target_date), at(START + 14 * 86400));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        let now = at(START);
+        let valid = ExportedReminder::from_reminder(
+            ChannelId::new(2),
+            &one_off(UserId::new(3), at(START + 60)),
+        );
+        let user_id = UserId::new(3);
+        assert!(valid.to_reminder(user_id, now).is_ok());
+
+        let past = ExportedReminder {
+            datetime: at(START - 60),
+            ..valid.clone()
+        };
+        assert!(past.to_reminder(user_id, now).is_err());
+        let too_often = ExportedReminder {
+            repeat: Some("every 1 minute".to_string()),
+            ..valid.clone()
+        };
+        assert!(too_often.to_reminder(user_id, now).is_err());
+        let never_sent = ExportedReminder {
+            repeat: Some("0 9 30 2 *".to_string()),
+            ..valid.clone()
+        };
+        assert!(never_sent.to_reminder(user_id, now).is_err());
+        let limit_without_repeat = ExportedReminder {
+            times: Some(3),
+            ..valid.clone()
+        };
+        assert!(limit_without_repeat.to_reminder(user_id, now).is_err());
+        let start_without_repeat = ExportedReminder {
+            start: Some(at(START)),
+            ..valid.clone()
+        };
+        assert!(start_without_repeat.to_reminder(user_id, now).is_err());
+        let bad_lead_time = ExportedReminder {
+            before: Some("soon".to_string()),
+            ..valid
+        };
+        assert!(bad_lead_time.to_reminder(user_id, now).is_err());
+
+        // unknown versions are refused before looking at the reminders
+        let newer = format!(
+            r#"{{"version": {}, "guild": "1", "reminders": []}}"#,
+            EXPORT_VERSION + 1
+        );
+        assert!(GuildExport::parse(newer.as_bytes(), ExportFormat::Json).is_err());
+    }
+}
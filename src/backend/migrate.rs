@@ -0,0 +1,244 @@
+use std::{collections::BTreeMap, io::Read, io::Write};
+
+use serde::Serialize;
+use serde_cbor::Value;
+
+use crate::{backend::data::ReminderTable, Error};
+
+/// Version of the format the reminder table is saved in.
+/// Bump it and add a migration whenever a change would stop older files from loading.
+/// The migrations only run on CBOR files. SQLite databases in an older version are refused,
+/// so they need an upgrade of their own as well.
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a table saved as version n to version n + 1
+const MIGRATIONS: [fn(Value) -> Result<Value, Error>; FORMAT_VERSION as usize] = [v0_to_v1];
+
+/// The table with the version it was saved as.
+/// Files without this header are version 0.
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u32,
+    table: &'a ReminderTable,
+}
+
+/// Writes the table in the current format
+pub(crate) fn encode<W: Write>(writer: W, table: &ReminderTable) -> Result<(), Error> {
+    let versioned = Versioned {
+        version: FORMAT_VERSION,
+        table,
+    };
+    serde_cbor::to_writer(writer, &versioned)?;
+    Ok(())
+}
+
+/// Reads a table saved in any version of the format, upgrading it to the current one
+pub(crate) fn decode<R: Read>(reader: R) -> Result<ReminderTable, Error> {
+    let value: Value = serde_cbor::from_reader(reader)?;
+    let (version, mut table) = split_version(value)?;
+    check_version(version)?;
+    for migration in &MIGRATIONS[version as usize..] {
+        table = migration(table)?;
+    }
+    Ok(serde_cbor::value::from_value(table)?)
+}
+
+/// Fails for versions saved by a newer release, which this one cannot read
+pub(crate) fn check_version(version: u32) -> Result<(), Error> {
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "Reminders were saved in format version {}, but only up to {} is supported.",
+            version, FORMAT_VERSION
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn text(s: &str) -> Value {
+    Value::Text(s.to_string())
+}
+
+/// Separates the version header from the table
+fn split_version(value: Value) -> Result<(u32, Value), Error> {
+    let Value::Map(mut map) = value else {
+        return Err("Saved reminders are not a table.".into());
+    };
+    match map.remove(&text("version")) {
+        None => Ok((0, Value::Map(map))),
+        Some(Value::Integer(version)) => {
+            let version = u32::try_from(version)?;
+            let table = map
+                .remove(&text("table"))
+                .ok_or("Saved reminders have a version but no table.")?;
+            Ok((version, table))
+        }
+        Some(_) => Err("Saved reminders have an invalid version.".into()),
+    }
+}
+
+/// Version 0 is every file saved before versions were added.
+/// The first of these had no reminder ids, and repeats only had an interval.
+fn v0_to_v1(mut table: Value) -> Result<Value, Error> {
+    let Value::Map(table_map) = &mut table else {
+        return Err("Saved reminders are not a table.".into());
+    };
+    let mut last_id = match table_map.get(&text("last_id")) {
+        Some(Value::Integer(last_id)) => *last_id,
+        _ => 0,
+    };
+
+    // reminders without an id are numbered after the ids already handed out
+    for key in ["map", "personal"] {
+        let Some(Value::Map(sets)) = table_map.get_mut(&text(key)) else {
+            continue;
+        };
+        for set in sets.values_mut() {
+            let Value::Array(set) = set else {
+                return Err("Saved reminders are not a list.".into());
+            };
+            for reminder in set {
+                let Value::Map(reminder) = reminder else {
+                    return Err("Saved reminder is not a map.".into());
+                };
+                if matches!(reminder.get(&text("id")), None | Some(Value::Integer(0))) {
+                    last_id += 1;
+                    reminder.insert(text("id"), Value::Integer(last_id));
+                }
+                if let Some(repeat) = reminder.get_mut(&text("repeating")) {
+                    interval_to_recurrence(repeat)?;
+                }
+            }
+        }
+    }
+
+    table_map.insert(text("last_id"), Value::Integer(last_id));
+    Ok(table)
+}
+
+/// Rewrites a repeat that only has an interval as every 1 of that interval
+fn interval_to_recurrence(repeat: &mut Value) -> Result<(), Error> {
+    let Value::Map(repeat) = repeat else {
+        // not repeating
+        return Ok(());
+    };
+    let Some(interval) = repeat.remove(&text("interval")) else {
+        // already has a recurrence
+        return Ok(());
+    };
+    let (interval, every) = match interval {
+        // only existed in debug builds
+        Value::Text(name) if name == "FiveMinutesly" => (text("Minutely"), 5),
+        Value::Text(name) => (Value::Text(name), 1),
+        _ => return Err("Saved repeat has an invalid interval.".into()),
+    };
+    let every = BTreeMap::from([
+        (text("interval"), interval),
+        (text("every"), Value::Integer(every)),
+    ]);
+    let recurrence = BTreeMap::from([(text("Every"), Value::Map(every))]);
+    repeat.insert(text("recurrence"), Value::Map(recurrence));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+
+    use crate::{
+        backend::data::{Interval, Recurrence, ReminderKey},
+        test_util::at,
+    };
+
+    /// Saved by the first release, before versions were added
+    const V0: &[u8] = include_bytes!("fixtures/v0.cbor");
+    /// Saved as version 1, with personal reminders and holidays
+    const V1: &[u8] = include_bytes!("fixtures/v1.cbor");
+
+    #[test]
+    fn decodes_v0() {
+        let table = decode(V0).unwrap();
+        let guild_id = GuildId::new(100);
+
+        let lab = ReminderKey::Channel(guild_id, ChannelId::new(200));
+        let [reminder] = table.get_reminders(lab).unwrap() else {
+            panic!("expected one reminder");
+        };
+        assert_eq!(reminder.name.as_deref(), Some("Lab report"));
+        assert_eq!(reminder.get_creation(), (at(1706000000), UserId::new(300)));
+        let repeat = reminder.repeating.as_ref().unwrap();
+        assert_eq!(
+            repeat.recurrence,
+            Recurrence::Every {
+                interval: Interval::Weekly,
+                every: 1
+            }
+        );
+        // already sent twice
+        assert_eq!(
+            repeat.next(&reminder.target_date),
+            at(1706691600 + 2 * 7 * 86400)
+        );
+
+        let quiz = ReminderKey::Channel(guild_id, ChannelId::new(201));
+        let reminders = table.get_reminders(quiz).unwrap();
+        assert_eq!(reminders.len(), 2);
+        let repeat = reminders
+            .iter()
+            .find_map(|reminder| reminder.repeating.as_ref())
+            .unwrap();
+        assert_eq!(
+            repeat.recurrence,
+            Recurrence::Every {
+                interval: Interval::Minutely,
+                every: 5
+            }
+        );
+
+        // every reminder gets its own id
+        let mut ids = table
+            .iter()
+            .flat_map(|(_, reminders)| reminders.iter().map(|reminder| reminder.id()))
+            .collect::<Vec<_>>();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn decodes_v1() {
+        let table = decode(V1).unwrap();
+        let guild_id = GuildId::new(100);
+        assert_eq!(table.holidays(guild_id).len(), 1);
+
+        let personal = ReminderKey::User(UserId::new(301));
+        let [reminder] = table.get_reminders(personal).unwrap() else {
+            panic!("expected one reminder");
+        };
+        assert_eq!(reminder.name.as_deref(), Some("Revise"));
+        assert_eq!(reminder.lead_times, vec![60]);
+        assert_eq!(table.iter().map(|(_, set)| set.len()).sum::<usize>(), 4);
+    }
+
+    #[test]
+    fn encodes_current_version() {
+        let table = decode(V1).unwrap();
+        let mut bytes = Vec::new();
+        encode(&mut bytes, &table).unwrap();
+        let (version, _) = split_version(serde_cbor::from_slice(&bytes).unwrap()).unwrap();
+        assert_eq!(version, FORMAT_VERSION);
+        assert_eq!(decode(bytes.as_slice()).unwrap(), table);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let table = BTreeMap::from([
+            (text("version"), Value::Integer(FORMAT_VERSION as i128 + 1)),
+            (text("table"), Value::Map(BTreeMap::new())),
+        ]);
+        let bytes = serde_cbor::to_vec(&Value::Map(table)).unwrap();
+        assert!(decode(bytes.as_slice()).is_err());
+    }
+}
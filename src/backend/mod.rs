@@ -1,12 +1,160 @@
+pub(crate) mod calendar;
+pub(crate) mod cron;
 pub(crate) mod data;
+pub(crate) mod datetime;
+pub(crate) mod export;
+pub(crate) mod migrate;
+pub(crate) mod rrule;
+pub(crate) mod shared;
+pub(crate) mod sqlite;
+pub(crate) mod store;
 
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
 
 use crate::{backend::data::ReminderTable, Error};
 
+/// Number of previous saves kept next to the reminder table
+pub(crate) const BACKUPS: u32 = 3;
+
+/// Loads the reminder table, or an empty one if it has never been saved.
+/// A file that exists but cannot be read is an error, so it is not overwritten by an empty table.
 pub(crate) fn load_data_from_path(path: &PathBuf) -> Result<ReminderTable, Error> {
-    let file = File::open(path)?;
-    let reminders: ReminderTable = serde_cbor::from_reader(file)?;
-    Ok(reminders)
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(ReminderTable::new()),
+        Err(e) => return Err(e.into()),
+    };
+    migrate::decode(BufReader::new(file))
+}
+
+/// Saves the reminder table without ever leaving a partly written file in its place.
+/// The table is written to a temporary file which replaces the old one once it is on disk,
+/// With `backup`, the previous save is kept as `<path>.1` (newest), moving older ones up to `<path>.<BACKUPS>`.
+pub(crate) fn save_data_to_path(
+    path: &PathBuf,
+    reminders: &ReminderTable,
+    backup: bool,
+) -> Result<(), Error> {
+    let temp_path = with_suffix(path, "tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        migrate::encode(&mut writer, reminders)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+
+    if backup && path.exists() {
+        for generation in (1..BACKUPS).rev() {
+            let backup = backup_path(path, generation);
+            if backup.exists() {
+                fs::rename(&backup, backup_path(path, generation + 1))?;
+            }
+        }
+        // copied rather than moved so there is always a table at the path
+        fs::copy(path, backup_path(path, 1))?;
+    }
+    fs::rename(&temp_path, path)?;
+
+    // the rename is only durable once the directory has been synced
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Path of one of the backups kept by `save_data_to_path`, 1 being the newest
+pub(crate) fn backup_path(path: &Path, generation: u32) -> PathBuf {
+    with_suffix(path, &generation.to_string())
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use poise::serenity_prelude::{ChannelId, GuildId, Timestamp, UserId};
+
+    use crate::{
+        backend::data::ReminderKey,
+        test_util::{one_off, test_dir},
+    };
+
+    fn table_with(reminders: u32) -> ReminderTable {
+        let key = ReminderKey::Channel(GuildId::new(1), ChannelId::new(2));
+        let mut table = ReminderTable::new();
+        for _ in 0..reminders {
+            let reminder = one_off(UserId::new(3), Timestamp::now());
+            table.add_reminder(key, reminder).unwrap();
+        }
+        table
+    }
+
+    #[test]
+    fn saves_and_rotates_backups() {
+        let dir = test_dir("backups");
+        let path = dir.join("reminder_table");
+        assert_eq!(load_data_from_path(&path).unwrap(), ReminderTable::new());
+
+        let tables = (1..=BACKUPS + 2).map(table_with).collect::<Vec<_>>();
+        for table in &tables {
+            save_data_to_path(&path, table, true).unwrap();
+        }
+        // newest first
+        let mut saved = tables.iter().rev();
+        assert_eq!(&load_data_from_path(&path).unwrap(), saved.next().unwrap());
+        for (generation, table) in (1..=BACKUPS).zip(saved) {
+            assert_eq!(
+                &load_data_from_path(&backup_path(&path, generation)).unwrap(),
+                table
+            );
+        }
+        assert!(!backup_path(&path, BACKUPS + 1).exists());
+        assert!(!with_suffix(&path, "tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saves_without_backup() {
+        let dir = test_dir("no-backup");
+        let path = dir.join("reminder_table");
+        let tables = (1..=3).map(table_with).collect::<Vec<_>>();
+        save_data_to_path(&path, &tables[0], true).unwrap();
+        save_data_to_path(&path, &tables[1], true).unwrap();
+        save_data_to_path(&path, &tables[2], false).unwrap();
+
+        assert_eq!(load_data_from_path(&path).unwrap(), tables[2]);
+        // still the save before the last backup
+        assert_eq!(
+            load_data_from_path(&backup_path(&path, 1)).unwrap(),
+            tables[0]
+        );
+        assert!(!backup_path(&path, 2).exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_corrupt_table() {
+        let dir = test_dir("corrupt");
+        let path = dir.join("reminder_table");
+        save_data_to_path(&path, &table_with(1), true).unwrap();
+
+        // cut short, as if the disk filled up while writing
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(load_data_from_path(&path).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,612 @@
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+use crate::Error;
+
+/// How many periods (days, weeks, months or years) to look through for the next occurrence
+const MAX_PERIODS: u32 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// A weekday in BYDAY, optionally limited to the nth one in the month or year, e.g. `-1FR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ByDay {
+    nth: Option<i32>,
+    weekday: Weekday,
+}
+
+/// A time in `UNTIL` or `EXDATE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum RuleTime {
+    /// Written with a trailing `Z`
+    Utc(NaiveDateTime),
+    /// A floating time, in the time zone of the repeat
+    Local(NaiveDateTime),
+}
+
+impl RuleTime {
+    fn local(&self, zone: Tz) -> NaiveDateTime {
+        match self {
+            RuleTime::Utc(utc) => zone.from_utc_datetime(utc).naive_local(),
+            RuleTime::Local(local) => *local,
+        }
+    }
+}
+
+/// An iCalendar recurrence rule (RFC 5545), e.g. `RRULE:FREQ=WEEKLY;BYDAY=TU,TH;UNTIL=20240322`.
+///
+/// Supports `FREQ` (daily and longer), `INTERVAL`, `UNTIL`, `COUNT`, `BYDAY`, `BYMONTHDAY` and
+/// `BYMONTH`, along with `EXDATE` dates to leave out.
+/// The start of the rule (`DTSTART`) is the reminder's own timestamp.
+/// Stored as the text it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct RRule {
+    source: String,
+    frequency: Frequency,
+    interval: u32,
+    until: Option<RuleTime>,
+    count: Option<u32>,
+    by_day: Vec<ByDay>,
+    by_month_day: Vec<i32>,
+    by_month: Vec<u32>,
+    /// Excluded occurrences
+    exdates: Vec<RuleTime>,
+    /// Excluded days, for `EXDATE;VALUE=DATE`
+    exdays: Vec<NaiveDate>,
+}
+
+impl RRule {
+    /// First occurrence after `after`, or the first one at all if `after` is None.
+    /// Times of day are taken from `start`. Both are local times in `zone`,
+    /// which UTC times in `UNTIL` and `EXDATE` are converted into.
+    pub fn next_after(
+        &self,
+        start: NaiveDateTime,
+        after: Option<NaiveDateTime>,
+        zone: Tz,
+    ) -> Option<NaiveDateTime> {
+        let until = self.until.map(|until| until.local(zone));
+        let exdates = self
+            .exdates
+            .iter()
+            .map(|exdate| exdate.local(zone))
+            .collect::<Vec<_>>();
+
+        // with a count every occurrence from the start has to be counted,
+        // otherwise it is fine to jump straight to the period containing `after`
+        let first_period = match (self.count, after) {
+            (None, Some(after)) if after > start => self.period_of(start.date(), after.date()),
+            _ => 0,
+        };
+
+        let mut counted = 0;
+        for period in first_period..first_period.saturating_add(MAX_PERIODS) {
+            let mut days = self.period_days(start.date(), period)?;
+            if !self.by_month.is_empty() {
+                days.retain(|day| self.by_month.contains(&day.month()));
+            }
+            days.sort();
+            days.dedup();
+
+            for day in days {
+                let occurrence = day.and_time(start.time());
+                if occurrence < start {
+                    continue;
+                }
+                if until.is_some_and(|until| occurrence > until) {
+                    return None;
+                }
+                // excluded occurrences still count towards the count
+                counted += 1;
+                if self.count.is_some_and(|count| counted > count) {
+                    return None;
+                }
+                if exdates.contains(&occurrence) || self.exdays.contains(&day) {
+                    continue;
+                }
+                if after.is_some_and(|after| occurrence <= after) {
+                    continue;
+                }
+                return Some(occurrence);
+            }
+        }
+        None
+    }
+
+    /// Index of the period that contains `date`
+    fn period_of(&self, start: NaiveDate, date: NaiveDate) -> u32 {
+        let periods = match self.frequency {
+            Frequency::Daily => (date - start).num_days(),
+            Frequency::Weekly => (week_start(date) - week_start(start)).num_days() / 7,
+            Frequency::Monthly => {
+                (date.year() as i64 * 12 + date.month() as i64)
+                    - (start.year() as i64 * 12 + start.month() as i64)
+            }
+            Frequency::Yearly => (date.year() - start.year()) as i64,
+        };
+        u32::try_from(periods / self.interval as i64).unwrap_or(0)
+    }
+
+    /// Candidate days in a period, before filtering by month
+    fn period_days(&self, start: NaiveDate, period: u32) -> Option<Vec<NaiveDate>> {
+        let steps = period.checked_mul(self.interval)?;
+
+        let days = match self.frequency {
+            Frequency::Daily => {
+                let day = start.checked_add_days(Days::new(steps as u64))?;
+                let weekday_matches = self.by_day.is_empty()
+                    || self
+                        .by_day
+                        .iter()
+                        .any(|by_day| by_day.weekday == day.weekday());
+                let month_day_matches = self.by_month_day.is_empty()
+                    || month_days(day, &self.by_month_day).contains(&day);
+                if weekday_matches && month_day_matches {
+                    vec![day]
+                } else {
+                    vec![]
+                }
+            }
+            Frequency::Weekly => {
+                let first = week_start(start).checked_add_days(Days::new(7 * steps as u64))?;
+                let week = (0..7)
+                    .filter_map(|i| first.checked_add_days(Days::new(i)))
+                    .collect::<Vec<_>>();
+                if self.by_day.is_empty() {
+                    week.into_iter()
+                        .filter(|day| day.weekday() == start.weekday())
+                        .collect()
+                } else {
+                    self.select_by_day(&week)
+                }
+            }
+            Frequency::Monthly => {
+                let first = first_of_month(start).checked_add_months(Months::new(steps))?;
+                self.month_candidates(first, start)
+            }
+            Frequency::Yearly => {
+                let year = start.year().checked_add(steps as i32)?;
+                if !self.by_month.is_empty() {
+                    self.by_month
+                        .iter()
+                        .filter_map(|&month| NaiveDate::from_ymd_opt(year, month, 1))
+                        .flat_map(|first| self.month_candidates(first, start))
+                        .collect()
+                } else if !self.by_month_day.is_empty() {
+                    (1..=12)
+                        .filter_map(|month| NaiveDate::from_ymd_opt(year, month, 1))
+                        .flat_map(|first| self.month_candidates(first, start))
+                        .collect()
+                } else if !self.by_day.is_empty() {
+                    // nth weekday of the whole year
+                    let days = NaiveDate::from_ymd_opt(year, 1, 1)?
+                        .iter_days()
+                        .take_while(|day| day.year() == year)
+                        .collect::<Vec<_>>();
+                    self.select_by_day(&days)
+                } else {
+                    // skipped in years without the day, e.g. 29th of February
+                    NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                        .into_iter()
+                        .collect()
+                }
+            }
+        };
+        Some(days)
+    }
+
+    /// Candidate days in the month starting at `first`
+    fn month_candidates(&self, first: NaiveDate, start: NaiveDate) -> Vec<NaiveDate> {
+        let days = first
+            .iter_days()
+            .take_while(|day| day.month() == first.month())
+            .collect::<Vec<_>>();
+
+        match (self.by_month_day.is_empty(), self.by_day.is_empty()) {
+            // same day of the month as the start, skipped in months without it
+            (true, true) => days
+                .into_iter()
+                .filter(|day| day.day() == start.day())
+                .collect(),
+            (false, true) => month_days(first, &self.by_month_day),
+            (true, false) => self.select_by_day(&days),
+            (false, false) => {
+                let month_days = month_days(first, &self.by_month_day);
+                self.select_by_day(&days)
+                    .into_iter()
+                    .filter(|day| month_days.contains(day))
+                    .collect()
+            }
+        }
+    }
+
+    /// Days in `days` matching BYDAY, where `nth` counts within `days`
+    fn select_by_day(&self, days: &[NaiveDate]) -> Vec<NaiveDate> {
+        let mut selected = Vec::new();
+        for by_day in &self.by_day {
+            let matching = days
+                .iter()
+                .filter(|day| day.weekday() == by_day.weekday)
+                .copied()
+                .collect::<Vec<_>>();
+            match by_day.nth {
+                None => selected.extend(matching),
+                Some(nth) => {
+                    let index = if nth > 0 {
+                        nth as i64 - 1
+                    } else {
+                        matching.len() as i64 + nth as i64
+                    };
+                    if let Some(day) = usize::try_from(index).ok().and_then(|i| matching.get(i)) {
+                        selected.push(*day);
+                    }
+                }
+            }
+        }
+        selected
+    }
+}
+
+fn week_start(date: NaiveDate) -> NaiveDate {
+    date.checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+        .unwrap_or(date)
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// Days in the month of `date` for BYMONTHDAY values, where negative values count from the end
+fn month_days(date: NaiveDate, by_month_day: &[i32]) -> Vec<NaiveDate> {
+    let first = first_of_month(date);
+    let length = first
+        .checked_add_months(Months::new(1))
+        .map_or(31, |next| (next - first).num_days() as i32);
+
+    by_month_day
+        .iter()
+        .filter_map(|&day| {
+            let day = if day < 0 { length + day + 1 } else { day };
+            first.with_day(u32::try_from(day).ok()?)
+        })
+        .collect()
+}
+
+/// A date or date-time as written in iCalendar, e.g. `20240322` or `20240322T090000Z`
+enum DateOrTime {
+    Date(NaiveDate),
+    DateTime(RuleTime),
+}
+
+fn parse_date_or_time(s: &str) -> Result<DateOrTime, Error> {
+    // times in UTC end with Z, floating times are in the repeat's time zone
+    let (s, utc) = match s.strip_suffix('Z') {
+        Some(s) => (s, true),
+        None => (s, false),
+    };
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S") {
+        return Ok(DateOrTime::DateTime(match utc {
+            true => RuleTime::Utc(date_time),
+            false => RuleTime::Local(date_time),
+        }));
+    }
+    match NaiveDate::parse_from_str(s, "%Y%m%d") {
+        Ok(date) => Ok(DateOrTime::Date(date)),
+        Err(_) => Err(format!("Invalid iCalendar date \"{}\".", s).into()),
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, Error> {
+    match s {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("Invalid weekday \"{}\" in BYDAY.", s).into()),
+    }
+}
+
+fn parse_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, Error> {
+    value
+        .split(',')
+        .map(|item| {
+            item.parse()
+                .map_err(|_| format!("Invalid value \"{}\" in {}.", item, key).into())
+        })
+        .collect()
+}
+
+impl FromStr for RRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let source = s.trim().to_string();
+
+        // the rule and any EXDATE lines can be separated by spaces or new lines
+        let mut rule = None;
+        let mut exdates = Vec::new();
+        for token in source.to_uppercase().split_whitespace() {
+            if let Some(value) = token.strip_prefix("RRULE:") {
+                rule = Some(value.to_string());
+            } else if token.starts_with("EXDATE") {
+                // EXDATE;VALUE=DATE:20240213,20240220
+                let Some((_, value)) = token.rsplit_once(':') else {
+                    return Err(format!("Invalid EXDATE \"{}\".", token).into());
+                };
+                exdates.extend(value.split(',').map(str::to_string));
+            } else if token.contains("FREQ=") {
+                rule = Some(token.to_string());
+            } else {
+                return Err(format!("Could not understand \"{}\" in RRULE.", token).into());
+            }
+        }
+        let Some(rule) = rule else {
+            return Err("RRULE is missing.".into());
+        };
+
+        let mut frequency = None;
+        let mut rrule = RRule {
+            source: source.clone(),
+            frequency: Frequency::Daily,
+            interval: 1,
+            until: None,
+            count: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            exdates: Vec::new(),
+            exdays: Vec::new(),
+        };
+
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let Some((key, value)) = part.split_once('=') else {
+                return Err(format!("Invalid RRULE part \"{}\".", part).into());
+            };
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("FREQ={} is not supported.", value).into()),
+                    })
+                }
+                "INTERVAL" => match value.parse() {
+                    Ok(interval) if interval > 0 => rrule.interval = interval,
+                    _ => return Err(format!("Invalid INTERVAL \"{}\".", value).into()),
+                },
+                "UNTIL" => {
+                    rrule.until = Some(match parse_date_or_time(value)? {
+                        DateOrTime::DateTime(date_time) => date_time,
+                        // a date includes the whole day
+                        DateOrTime::Date(date) => {
+                            RuleTime::Local(date.and_hms_opt(23, 59, 59).unwrap())
+                        }
+                    })
+                }
+                "COUNT" => match value.parse() {
+                    Ok(count) if count > 0 => rrule.count = Some(count),
+                    _ => return Err(format!("Invalid COUNT \"{}\".", value).into()),
+                },
+                "BYDAY" => {
+                    for item in value.split(',') {
+                        let split = item.len().saturating_sub(2);
+                        let (nth, weekday) = item.split_at(split);
+                        let nth = match nth {
+                            "" => None,
+                            nth => match nth.trim_start_matches('+').parse::<i32>() {
+                                Ok(nth) if nth != 0 && nth.abs() <= 53 => Some(nth),
+                                _ => return Err(format!("Invalid BYDAY \"{}\".", item).into()),
+                            },
+                        };
+                        rrule.by_day.push(ByDay {
+                            nth,
+                            weekday: parse_weekday(weekday)?,
+                        });
+                    }
+                }
+                "BYMONTHDAY" => {
+                    rrule.by_month_day = parse_list(key, value)?;
+                    if rrule
+                        .by_month_day
+                        .iter()
+                        .any(|day| *day == 0 || day.abs() > 31)
+                    {
+                        return Err(format!("Invalid BYMONTHDAY \"{}\".", value).into());
+                    }
+                }
+                "BYMONTH" => {
+                    rrule.by_month = parse_list(key, value)?;
+                    if rrule.by_month.iter().any(|month| !(1..=12).contains(month)) {
+                        return Err(format!("Invalid BYMONTH \"{}\".", value).into());
+                    }
+                }
+                // weeks always start on Monday
+                "WKST" => {}
+                "EXDATE" => exdates.extend(value.split(',').map(str::to_string)),
+                _ => return Err(format!("{} is not supported in RRULE.", key).into()),
+            }
+        }
+
+        rrule.frequency = frequency.ok_or("RRULE is missing FREQ.")?;
+        if rrule.until.is_some() && rrule.count.is_some() {
+            return Err("RRULE cannot have both UNTIL and COUNT.".into());
+        }
+
+        for exdate in exdates {
+            match parse_date_or_time(&exdate)? {
+                DateOrTime::DateTime(date_time) => rrule.exdates.push(date_time),
+                DateOrTime::Date(date) => rrule.exdays.push(date),
+            }
+        }
+
+        Ok(rrule)
+    }
+}
+
+impl TryFrom<String> for RRule {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RRule> for String {
+    fn from(value: RRule) -> Self {
+        value.source
+    }
+}
+
+impl Display for RRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first `count` occurrences from `start`, in local time
+    fn occurrences(rule: &str, start: &str, zone: Tz, count: usize) -> Vec<String> {
+        let rrule = rule.parse::<RRule>().unwrap();
+        let start = NaiveDateTime::parse_from_str(start, "%Y-%m-%d %H:%M").unwrap();
+        let mut times = Vec::new();
+        let mut after = None;
+        while times.len() < count {
+            let Some(next) = rrule.next_after(start, after, zone) else {
+                break;
+            };
+            times.push(next.format("%Y-%m-%d %H:%M").to_string());
+            after = Some(next);
+        }
+        times
+    }
+
+    #[test]
+    fn by_day() {
+        // 2024-01-30 is a Tuesday
+        assert_eq!(
+            occurrences(
+                "RRULE:FREQ=WEEKLY;BYDAY=TU,TH",
+                "2024-01-30 09:00",
+                Tz::UTC,
+                4
+            ),
+            [
+                "2024-01-30 09:00",
+                "2024-02-01 09:00",
+                "2024-02-06 09:00",
+                "2024-02-08 09:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn last_friday() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY;BYDAY=-1FR", "2024-01-01 09:00", Tz::UTC, 3),
+            ["2024-01-26 09:00", "2024-02-23 09:00", "2024-03-29 09:00"]
+        );
+    }
+
+    #[test]
+    fn month_day_skips_short_months() {
+        assert_eq!(
+            occurrences("FREQ=MONTHLY;BYMONTHDAY=31", "2024-01-01 09:00", Tz::UTC, 3),
+            ["2024-01-31 09:00", "2024-03-31 09:00", "2024-05-31 09:00"]
+        );
+    }
+
+    #[test]
+    fn interval() {
+        assert_eq!(
+            occurrences("FREQ=WEEKLY;INTERVAL=2", "2024-01-31 09:00", Tz::UTC, 3),
+            ["2024-01-31 09:00", "2024-02-14 09:00", "2024-02-28 09:00"]
+        );
+    }
+
+    #[test]
+    fn excluded_dates_still_count() {
+        assert_eq!(
+            occurrences(
+                "RRULE:FREQ=DAILY;COUNT=4 EXDATE:20240202T090000",
+                "2024-02-01 09:00",
+                Tz::UTC,
+                10
+            ),
+            ["2024-02-01 09:00", "2024-02-03 09:00", "2024-02-04 09:00"]
+        );
+    }
+
+    #[test]
+    fn utc_times_in_other_zones() {
+        let zone = chrono_tz::America::New_York;
+        // 13:00 UTC is 08:00 in New York, before the 09:00 occurrence on the 3rd
+        assert_eq!(
+            occurrences(
+                "FREQ=DAILY;UNTIL=20240303T130000Z",
+                "2024-03-01 09:00",
+                zone,
+                10
+            ),
+            ["2024-03-01 09:00", "2024-03-02 09:00"]
+        );
+        // 14:00 UTC is 09:00 in New York
+        assert_eq!(
+            occurrences(
+                "FREQ=DAILY;UNTIL=20240303T140000Z",
+                "2024-03-01 09:00",
+                zone,
+                10
+            )
+            .len(),
+            3
+        );
+        // floating times are already local
+        assert_eq!(
+            occurrences(
+                "FREQ=DAILY;UNTIL=20240303T090000",
+                "2024-03-01 09:00",
+                zone,
+                10
+            )
+            .len(),
+            3
+        );
+        assert_eq!(
+            occurrences(
+                "RRULE:FREQ=DAILY;COUNT=3 EXDATE:20240302T140000Z",
+                "2024-03-01 09:00",
+                zone,
+                10
+            ),
+            ["2024-03-01 09:00", "2024-03-03 09:00"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!("FREQ=DAILY;COUNT=3;UNTIL=20240303"
+            .parse::<RRule>()
+            .is_err());
+        assert!("FREQ=HOURLY".parse::<RRule>().is_err());
+        assert!("BYDAY=MO".parse::<RRule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=XX".parse::<RRule>().is_err());
+        assert!("FREQ=MONTHLY;BYMONTHDAY=32".parse::<RRule>().is_err());
+    }
+}
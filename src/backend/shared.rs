@@ -0,0 +1,188 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, LockResult, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use tokio::sync::Notify;
+
+use crate::{
+    backend::{
+        data::{Reminder, ReminderKey, ReminderTable},
+        store::{changes, ReminderFilter, ReminderStore},
+    },
+    Error,
+};
+
+/// How long to wait after a change before saving, so a burst of changes is saved once
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+/// How long to wait before trying again after a failed save
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Reminder table shared between commands and the scheduler, saved to disk whenever it changes
+pub(crate) struct SharedTable {
+    table: Mutex<ReminderTable>,
+    store: Box<dyn ReminderStore>,
+    /// Signalled when the table has been changed since the last save
+    changed: Notify,
+    /// Set while there are changes the store does not have yet
+    dirty: AtomicBool,
+    /// The table as the store last saved it, to work out which reminders changed since.
+    /// Held while saving, so saves from the background task and shutdown cannot interleave.
+    saved: Mutex<ReminderTable>,
+}
+
+/// Lock on a `SharedTable`. Any mutable access marks the table to be saved once released.
+pub(crate) struct TableGuard<'a> {
+    guard: MutexGuard<'a, ReminderTable>,
+    changed: &'a Notify,
+    dirty: &'a AtomicBool,
+    modified: bool,
+}
+
+impl Deref for TableGuard<'_> {
+    type Target = ReminderTable;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl DerefMut for TableGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.modified = true;
+        &mut self.guard
+    }
+}
+
+impl Drop for TableGuard<'_> {
+    fn drop(&mut self) {
+        if self.modified {
+            self.dirty.store(true, Ordering::SeqCst);
+            self.changed.notify_one();
+        }
+    }
+}
+
+impl SharedTable {
+    pub fn new(table: ReminderTable, store: Box<dyn ReminderStore>) -> Self {
+        Self {
+            saved: Mutex::new(table.clone()),
+            table: Mutex::new(table),
+            store,
+            changed: Notify::new(),
+            dirty: AtomicBool::new(false),
+        }
+    }
+
+    /// Locks the table, like `Mutex::lock`
+    pub fn lock(&self) -> LockResult<TableGuard<'_>> {
+        let wrap = |guard| TableGuard {
+            guard,
+            changed: &self.changed,
+            dirty: &self.dirty,
+            modified: false,
+        };
+        match self.table.lock() {
+            Ok(guard) => Ok(wrap(guard)),
+            Err(poisoned) => Err(PoisonError::new(wrap(poisoned.into_inner()))),
+        }
+    }
+
+    /// Writes the changes since the last save to the store now
+    pub fn save(&self) -> Result<(), Error> {
+        let mut saved = self.saved.lock().unwrap();
+        let copy = {
+            let lock = self.lock().unwrap();
+            // cleared while locked, so a change made after the copy marks it dirty again
+            self.dirty.store(false, Ordering::SeqCst);
+            lock.clone()
+        };
+        let result = self.store.save_changes(&copy, &changes(&saved, &copy));
+        match result {
+            Ok(()) => *saved = copy,
+            Err(_) => self.dirty.store(true, Ordering::SeqCst),
+        }
+        result
+    }
+
+    /// Finds reminders, soonest due first.
+    /// Stores that can be queried are searched after saving any changes they do not have yet.
+    pub fn query(&self, filter: &ReminderFilter) -> Result<Vec<(ReminderKey, Reminder)>, Error> {
+        if !self.store.can_query() {
+            return Ok(filter.apply(&self.lock().unwrap()));
+        }
+        if self.dirty.load(Ordering::SeqCst) {
+            self.save()?;
+        }
+        self.store.query(filter)
+    }
+
+    /// Spawns a task that saves the table shortly after each change
+    pub fn start_saving(self: &Arc<Self>) {
+        let table = self.clone();
+        tokio::spawn(async move {
+            loop {
+                table.changed.notified().await;
+                tokio::time::sleep(SAVE_DELAY).await;
+                // already saved, e.g. before a query
+                if !table.dirty.load(Ordering::SeqCst) {
+                    continue;
+                }
+
+                let saving = table.clone();
+                match tokio::task::spawn_blocking(move || saving.save()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        // what was saved before is left as it was, so try again later
+                        eprintln!("Failed to save reminders: {}", e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                        table.changed.notify_one();
+                    }
+                    Err(e) => eprintln!("Failed to save reminders: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use poise::serenity_prelude::{ChannelId, GuildId, Timestamp, UserId};
+
+    use crate::{
+        backend::{load_data_from_path, store::CborStore},
+        test_util::{one_off, test_dir},
+    };
+
+    #[tokio::test]
+    async fn saves_after_changes() {
+        let dir = test_dir("shared");
+        let path = dir.join("reminder_table");
+
+        let store = Box::new(CborStore::new(path.clone()));
+        let table = Arc::new(SharedTable::new(ReminderTable::new(), store));
+        table.start_saving();
+
+        // reading does not need a save
+        let _ = table.lock().unwrap().iter().count();
+        let key = ReminderKey::Channel(GuildId::new(1), ChannelId::new(2));
+        let reminder = one_off(UserId::new(3), Timestamp::now());
+        table.lock().unwrap().add_reminder(key, reminder).unwrap();
+
+        let expected = table.lock().unwrap().clone();
+        for _ in 0..50 {
+            if path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(load_data_from_path(&path).unwrap(), expected);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,378 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+use rusqlite::{params, types::Value, Connection, OptionalExtension};
+
+use crate::{
+    backend::{
+        data::{Reminder, ReminderKey, ReminderTable},
+        migrate::{check_version, FORMAT_VERSION},
+        store::{Change, ReminderFilter, ReminderStore},
+    },
+    Error,
+};
+
+/// Reminders have a row each, with columns for what they are queried by.
+/// Everything else in the table, like holidays and time zones, is kept in a single row.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS reminders (
+    id INTEGER PRIMARY KEY,
+    guild_id INTEGER,
+    channel_id INTEGER,
+    user_id INTEGER,
+    created_by INTEGER NOT NULL,
+    due INTEGER NOT NULL,
+    reminder BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS reminders_guild ON reminders (guild_id);
+CREATE INDEX IF NOT EXISTS reminders_created_by ON reminders (created_by);
+CREATE INDEX IF NOT EXISTS reminders_due ON reminders (due);
+CREATE TABLE IF NOT EXISTS settings (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    data BLOB NOT NULL
+);
+";
+
+/// Format of databases made before it was recorded in `user_version`.
+/// The SQLite store was added while reminders were saved in version 1.
+const UNVERSIONED_FORMAT: u32 = 1;
+
+/// Reminders in an embedded SQLite database
+pub(crate) struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// A store that only lasts as long as it is open
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self, Error> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    /// Creates the tables, recording the format version in SQLite's `user_version`.
+    /// Rows are read as they are, without the migrations that upgrade a CBOR file,
+    /// so databases saved in an older format are refused rather than misread.
+    fn init(connection: Connection) -> Result<Self, Error> {
+        let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let existing: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'reminders')",
+            [],
+            |row| row.get(0),
+        )?;
+        let version = match (existing, version) {
+            (false, _) => FORMAT_VERSION,
+            (true, 0) => UNVERSIONED_FORMAT,
+            (true, version) => version,
+        };
+        check_version(version)?;
+        if version < FORMAT_VERSION {
+            return Err(format!(
+                "Reminders were saved in format version {}, which the SQLite store cannot upgrade to {}.",
+                version, FORMAT_VERSION
+            )
+            .into());
+        }
+        connection.execute_batch(SCHEMA)?;
+        connection.pragma_update(None, "user_version", version)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+// discord ids fit in 63 bits, so are stored as SQLite's signed integers
+fn to_sql(id: u64) -> i64 {
+    id as i64
+}
+
+fn from_sql(id: Option<i64>) -> Option<u64> {
+    id.map(|id| id as u64)
+}
+
+/// Works out which guild/channel pair or user a row belongs to
+fn row_key(
+    guild_id: Option<i64>,
+    channel_id: Option<i64>,
+    user_id: Option<i64>,
+) -> Result<ReminderKey, Error> {
+    match (from_sql(guild_id), from_sql(channel_id), from_sql(user_id)) {
+        (Some(guild_id), Some(channel_id), None) => Ok(ReminderKey::Channel(
+            GuildId::new(guild_id),
+            ChannelId::new(channel_id),
+        )),
+        (None, None, Some(user_id)) => Ok(ReminderKey::User(UserId::new(user_id))),
+        _ => Err("Stored reminder has neither a channel nor a user.".into()),
+    }
+}
+
+/// Writes everything in the table but the reminders, which is small enough to write each time
+fn save_settings(connection: &Connection, table: &ReminderTable) -> Result<(), Error> {
+    connection.execute(
+        "INSERT OR REPLACE INTO settings (id, data) VALUES (0, ?1)",
+        params![serde_cbor::to_vec(&table.settings())?],
+    )?;
+    Ok(())
+}
+
+/// Writes the row of a reminder, replacing the one with its id if there is one
+fn upsert(connection: &Connection, key: ReminderKey, reminder: &Reminder) -> Result<(), Error> {
+    let (guild_id, channel_id, user_id) = match key {
+        ReminderKey::Channel(guild_id, channel_id) => (
+            Some(to_sql(guild_id.get())),
+            Some(to_sql(channel_id.get())),
+            None,
+        ),
+        ReminderKey::User(user_id) => (None, None, Some(to_sql(user_id.get()))),
+    };
+    let mut statement = connection.prepare_cached(
+        "INSERT OR REPLACE INTO reminders (id, guild_id, channel_id, user_id, created_by, due, reminder)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?;
+    statement.execute(params![
+        reminder.id().get(),
+        guild_id,
+        channel_id,
+        user_id,
+        to_sql(reminder.get_creation().1.get()),
+        reminder.next_due().unix_timestamp(),
+        serde_cbor::to_vec(reminder)?,
+    ])?;
+    Ok(())
+}
+
+impl ReminderStore for SqliteStore {
+    fn load(&self) -> Result<ReminderTable, Error> {
+        let connection = self.connection.lock().unwrap();
+        let settings: Option<Vec<u8>> = connection
+            .query_row("SELECT data FROM settings WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        let mut table = match settings {
+            Some(settings) => serde_cbor::from_slice(&settings)?,
+            None => ReminderTable::new(),
+        };
+
+        let mut statement = connection
+            .prepare("SELECT guild_id, channel_id, user_id, reminder FROM reminders ORDER BY id")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let key = row_key(row.get(0)?, row.get(1)?, row.get(2)?)?;
+            let reminder: Vec<u8> = row.get(3)?;
+            table.insert_reminder(key, serde_cbor::from_slice(&reminder)?);
+        }
+        Ok(table)
+    }
+
+    fn save(&self, table: &ReminderTable) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        // all or nothing, so a failed save leaves the last one in place
+        let transaction = connection.transaction()?;
+        save_settings(&transaction, table)?;
+        transaction.execute("DELETE FROM reminders", [])?;
+        for (key, reminders) in table.iter() {
+            for reminder in reminders {
+                upsert(&transaction, key, reminder)?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn save_changes(&self, table: &ReminderTable, changes: &[Change]) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        save_settings(&transaction, table)?;
+        for change in changes {
+            match change {
+                Change::Upsert(key, reminder) => upsert(&transaction, *key, reminder)?,
+                Change::Delete(id) => {
+                    transaction.execute("DELETE FROM reminders WHERE id = ?1", [id.get()])?;
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn can_query(&self) -> bool {
+        true
+    }
+
+    fn query(&self, filter: &ReminderFilter) -> Result<Vec<(ReminderKey, Reminder)>, Error> {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+        if let Some(guild_id) = filter.guild_id {
+            conditions.push("guild_id = ?");
+            values.push(Value::Integer(to_sql(guild_id.get())));
+        }
+        if let Some(user_id) = filter.created_by {
+            conditions.push("created_by = ?");
+            values.push(Value::Integer(to_sql(user_id.get())));
+        }
+        if let Some(due_before) = filter.due_before {
+            conditions.push("due < ?");
+            values.push(Value::Integer(due_before.unix_timestamp()));
+        }
+        let mut sql = "SELECT guild_id, channel_id, user_id, reminder FROM reminders".to_string();
+        if !conditions.is_empty() {
+            sql += " WHERE ";
+            sql += &conditions.join(" AND ");
+        }
+        sql += " ORDER BY due";
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&sql)?;
+        let mut rows = statement.query(rusqlite::params_from_iter(values))?;
+        let mut reminders = Vec::new();
+        while let Some(row) = rows.next()? {
+            let key = row_key(row.get(0)?, row.get(1)?, row.get(2)?)?;
+            let reminder: Vec<u8> = row.get(3)?;
+            reminders.push((key, serde_cbor::from_slice(&reminder)?));
+        }
+        Ok(reminders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        backend::{
+            data::{DateRange, Interval, Repeat},
+            store::changes,
+        },
+        test_util::{at, one_off, test_dir, START},
+    };
+
+    #[test]
+    fn saves_and_queries() {
+        let store = SqliteStore::in_memory().unwrap();
+        assert_eq!(store.load().unwrap(), ReminderTable::new());
+
+        let guild_id = GuildId::new(1);
+        let channel = ReminderKey::Channel(guild_id, ChannelId::new(2));
+        let (alice, bob) = (UserId::new(3), UserId::new(4));
+        let personal = ReminderKey::User(bob);
+
+        let mut table = ReminderTable::new();
+        let range: DateRange = "2024-02-19..2024-02-23".parse().unwrap();
+        table.set_holiday(guild_id, "Break".to_string(), range);
+        let mut weekly = one_off(alice, at(START));
+        weekly.repeating = Some(Repeat::new(Interval::Weekly, 1).unwrap());
+        table.add_reminder(channel, weekly).unwrap();
+        let soon = one_off(bob, at(1706000000));
+        table.add_reminder(channel, soon).unwrap();
+        let mine = one_off(bob, at(1707000000));
+        let mine = table.add_reminder(personal, mine).unwrap().id();
+
+        store.save(&table).unwrap();
+        assert_eq!(store.load().unwrap(), table);
+
+        let query = |filter: ReminderFilter| {
+            store
+                .query(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|(key, reminder)| (key, reminder.get_creation().1))
+                .collect::<Vec<_>>()
+        };
+        // ordered by when they are due
+        assert_eq!(
+            query(ReminderFilter {
+                guild_id: Some(guild_id),
+                ..Default::default()
+            }),
+            vec![(channel, bob), (channel, alice)]
+        );
+        assert_eq!(
+            query(ReminderFilter {
+                created_by: Some(bob),
+                ..Default::default()
+            }),
+            vec![(channel, bob), (personal, bob)]
+        );
+        assert_eq!(
+            query(ReminderFilter {
+                due_before: Some(at(START)),
+                ..Default::default()
+            }),
+            vec![(channel, bob)]
+        );
+
+        // saving replaces what was there
+        table.remove_reminder(personal, mine).unwrap();
+        store.save(&table).unwrap();
+        assert_eq!(store.load().unwrap(), table);
+        assert_eq!(
+            query(ReminderFilter {
+                created_by: Some(bob),
+                ..Default::default()
+            }),
+            vec![(channel, bob)]
+        );
+    }
+
+    #[test]
+    fn saves_changes() {
+        let store = SqliteStore::in_memory().unwrap();
+        let channel = ReminderKey::Channel(GuildId::new(1), ChannelId::new(2));
+        let mut table = ReminderTable::new();
+        for unix in [1706000000, 1707000000] {
+            let reminder = one_off(UserId::new(3), at(unix));
+            table.add_reminder(channel, reminder).unwrap();
+        }
+        store.save(&table).unwrap();
+
+        let saved = table.clone();
+        let first = table.iter().next().unwrap().1[0].id();
+        table.remove_reminder(channel, first).unwrap();
+        let added = one_off(UserId::new(4), at(1708000000));
+        table.add_reminder(channel, added).unwrap();
+        store
+            .save_changes(&table, &changes(&saved, &table))
+            .unwrap();
+        assert_eq!(store.load().unwrap(), table);
+    }
+
+    #[test]
+    fn versions_existing_databases() {
+        let dir = test_dir("sqlite-old");
+        let path = dir.join("reminders.sqlite");
+        let user_version = |path: &PathBuf| -> u32 {
+            Connection::open(path)
+                .unwrap()
+                .query_row("PRAGMA user_version", [], |row| row.get(0))
+                .unwrap()
+        };
+        drop(SqliteStore::open(path.clone()).unwrap());
+        assert_eq!(user_version(&path), FORMAT_VERSION);
+
+        // saved before the version was recorded
+        let connection = Connection::open(&path).unwrap();
+        connection.pragma_update(None, "user_version", 0).unwrap();
+        drop(connection);
+        drop(SqliteStore::open(path.clone()).unwrap());
+        assert_eq!(user_version(&path), UNVERSIONED_FORMAT);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_newer_versions() {
+        let dir = test_dir("sqlite-newer");
+        let path = dir.join("reminders.sqlite");
+        drop(SqliteStore::open(path.clone()).unwrap());
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .pragma_update(None, "user_version", FORMAT_VERSION + 1)
+            .unwrap();
+        drop(connection);
+        assert!(SqliteStore::open(path.clone()).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
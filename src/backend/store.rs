@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use poise::serenity_prelude::{GuildId, Timestamp, UserId};
+
+use crate::{
+    backend::{
+        backup_path,
+        data::{Reminder, ReminderId, ReminderKey, ReminderTable},
+        load_data_from_path, save_data_to_path,
+        sqlite::SqliteStore,
+        BACKUPS,
+    },
+    Error,
+};
+
+/// Which reminders to return from `ReminderStore::query`.
+/// Each filter that is set must match.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReminderFilter {
+    pub guild_id: Option<GuildId>,
+    pub created_by: Option<UserId>,
+    /// Only reminders next due before this time
+    pub due_before: Option<Timestamp>,
+}
+
+impl ReminderFilter {
+    pub fn matches(&self, key: ReminderKey, reminder: &Reminder) -> bool {
+        self.guild_id
+            .is_none_or(|guild_id| key.guild_id() == Some(guild_id))
+            && self
+                .created_by
+                .is_none_or(|user_id| reminder.get_creation().1 == user_id)
+            && self
+                .due_before
+                .is_none_or(|due_before| reminder.next_due() < due_before)
+    }
+
+    /// Finds the matching reminders in a table, soonest due first
+    pub fn apply(&self, table: &ReminderTable) -> Vec<(ReminderKey, Reminder)> {
+        let mut reminders = reminders_by_id(table)
+            .into_values()
+            .filter(|(key, reminder)| self.matches(*key, reminder))
+            .map(|(key, reminder)| (key, reminder.clone()))
+            .collect::<Vec<_>>();
+        reminders.sort_by_key(|(_, reminder)| (reminder.next_due(), reminder.id()));
+        reminders
+    }
+}
+
+/// A reminder that changed between two saves of the table
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Change<'a> {
+    /// Added or edited
+    Upsert(ReminderKey, &'a Reminder),
+    Delete(ReminderId),
+}
+
+fn reminders_by_id(table: &ReminderTable) -> HashMap<ReminderId, (ReminderKey, &Reminder)> {
+    table
+        .iter()
+        .flat_map(|(key, reminders)| {
+            reminders
+                .iter()
+                .map(move |reminder| (reminder.id(), (key, reminder)))
+        })
+        .collect()
+}
+
+/// Reminders that have to be written or deleted to turn the saved table into the current one
+pub(crate) fn changes<'a>(saved: &ReminderTable, current: &'a ReminderTable) -> Vec<Change<'a>> {
+    let saved = reminders_by_id(saved);
+    let current = reminders_by_id(current);
+    let mut changes = current
+        .iter()
+        .filter(|(id, now)| saved.get(id) != Some(now))
+        .map(|(_, (key, reminder))| Change::Upsert(*key, reminder))
+        .chain(
+            saved
+                .keys()
+                .filter(|id| !current.contains_key(id))
+                .map(|id| Change::Delete(*id)),
+        )
+        .collect::<Vec<_>>();
+    // in a fixed order, so the same changes are always written the same way
+    changes.sort_by_key(|change| match change {
+        Change::Upsert(_, reminder) => reminder.id(),
+        Change::Delete(id) => *id,
+    });
+    changes
+}
+
+/// Somewhere the reminder table is saved between runs
+pub(crate) trait ReminderStore: Send + Sync {
+    /// Reads the whole table. Fails rather than returning an empty table if it cannot be read.
+    fn load(&self) -> Result<ReminderTable, Error>;
+
+    /// Replaces what is stored with the table
+    fn save(&self, table: &ReminderTable) -> Result<(), Error>;
+
+    /// Brings what is stored up to date with the table, only writing the reminders that changed.
+    /// Stores that keep the table as a whole save all of it.
+    fn save_changes(&self, table: &ReminderTable, _changes: &[Change]) -> Result<(), Error> {
+        self.save(table)
+    }
+
+    /// Whether `query` can search the store itself.
+    /// Otherwise the table already in memory is searched instead.
+    fn can_query(&self) -> bool {
+        false
+    }
+
+    /// Finds stored reminders without going through the whole table, soonest due first
+    fn query(&self, _filter: &ReminderFilter) -> Result<Vec<(ReminderKey, Reminder)>, Error> {
+        Err("This reminder store cannot be queried.".into())
+    }
+}
+
+/// Least time between backups of the CBOR file. Saves follow every change,
+/// so backing up each one would soon leave only copies of the last few minutes.
+const BACKUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The whole table as one CBOR file, with rotating backups
+pub(crate) struct CborStore {
+    path: PathBuf,
+    /// When the last backup was made. The first save after starting always makes one.
+    last_backup: Mutex<Option<Instant>>,
+}
+
+impl CborStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            last_backup: Mutex::new(None),
+        }
+    }
+}
+
+impl ReminderStore for CborStore {
+    fn load(&self) -> Result<ReminderTable, Error> {
+        load_data_from_path(&self.path).map_err(|e| {
+            format!(
+                "{} (backups are kept as {} to .{})",
+                e,
+                backup_path(&self.path, 1).display(),
+                BACKUPS
+            )
+            .into()
+        })
+    }
+
+    fn save(&self, table: &ReminderTable) -> Result<(), Error> {
+        let mut last_backup = self.last_backup.lock().unwrap();
+        let backup = last_backup.is_none_or(|last| last.elapsed() >= BACKUP_INTERVAL);
+        save_data_to_path(&self.path, table, backup)?;
+        if backup {
+            *last_backup = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+/// Opens the store named in the config, "cbor" or "sqlite".
+/// Without a path, the store is kept in the working directory.
+pub(crate) fn open_store(
+    kind: &str,
+    path: Option<PathBuf>,
+) -> Result<Box<dyn ReminderStore>, Error> {
+    match kind.trim().to_lowercase().as_str() {
+        "cbor" => Ok(Box::new(CborStore::new(
+            path.unwrap_or_else(|| PathBuf::from("./reminder_table")),
+        ))),
+        "sqlite" => Ok(Box::new(SqliteStore::open(
+            path.unwrap_or_else(|| PathBuf::from("./reminders.sqlite")),
+        )?)),
+        _ => Err(format!("Unknown reminder store \"{}\", use cbor or sqlite.", kind).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use poise::serenity_prelude::ChannelId;
+
+    use crate::test_util::{at, one_off};
+
+    #[test]
+    fn finds_changed_reminders() {
+        let channel = ReminderKey::Channel(GuildId::new(1), ChannelId::new(2));
+        let personal = ReminderKey::User(UserId::new(3));
+        let mut saved = ReminderTable::new();
+        let kept = one_off(UserId::new(3), at(1706000000));
+        saved.add_reminder(channel, kept).unwrap();
+        let edited = one_off(UserId::new(3), at(1707000000));
+        let edited = saved.add_reminder(channel, edited).unwrap().id();
+        let removed = one_off(UserId::new(3), at(1708000000));
+        let removed = saved.add_reminder(personal, removed).unwrap().id();
+        assert_eq!(changes(&saved, &saved), vec![]);
+
+        let mut current = saved.clone();
+        current.remove_reminder(personal, removed).unwrap();
+        let mut reminder = current.remove_reminder(channel, edited).unwrap();
+        reminder.name = Some("Edited".to_string());
+        current.insert_reminder(channel, reminder.clone());
+        let added = one_off(UserId::new(3), at(1709000000));
+        let added = current.add_reminder(personal, added).unwrap().clone();
+
+        // the reminder left as it was is not written again
+        assert_eq!(
+            changes(&saved, &current),
+            vec![
+                Change::Upsert(channel, &reminder),
+                Change::Delete(removed),
+                Change::Upsert(personal, &added),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_table() {
+        let guild_id = GuildId::new(1);
+        let channel = ReminderKey::Channel(guild_id, ChannelId::new(2));
+        let (alice, bob) = (UserId::new(3), UserId::new(4));
+        let mut table = ReminderTable::new();
+        for (user_id, unix) in [(alice, 1707000000), (bob, 1706000000)] {
+            let reminder = one_off(user_id, at(unix));
+            table.add_reminder(channel, reminder).unwrap();
+        }
+        let personal = one_off(bob, at(1705000000));
+        table
+            .add_reminder(ReminderKey::User(bob), personal)
+            .unwrap();
+
+        let found = |filter: ReminderFilter| {
+            filter
+                .apply(&table)
+                .into_iter()
+                .map(|(_, reminder)| reminder.next_due().unix_timestamp())
+                .collect::<Vec<_>>()
+        };
+        // soonest due first
+        assert_eq!(
+            found(ReminderFilter::default()),
+            vec![1705000000, 1706000000, 1707000000]
+        );
+        assert_eq!(
+            found(ReminderFilter {
+                guild_id: Some(guild_id),
+                created_by: Some(bob),
+                ..Default::default()
+            }),
+            vec![1706000000]
+        );
+        assert_eq!(
+            found(ReminderFilter {
+                due_before: Some(at(1706500000)),
+                ..Default::default()
+            }),
+            vec![1705000000, 1706000000]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use poise::serenity_prelude::{
+    self as serenity, parse_user_mention, ButtonStyle, ComponentInteraction, CreateActionRow,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    FormattedTimestamp, FormattedTimestampStyle, Mention, Timestamp,
+};
+
+use crate::{
+    backend::data::{Reminder, ReminderId, ReminderKey},
+    Error, UserData,
+};
+
+/// Prefix of the custom ids of reminder buttons
+const PREFIX: &str = "reminder";
+/// Name of the embed field listing who has marked a reminder as done
+const DONE_FIELD: &str = "Done";
+/// Snooze buttons, as the label and the number of seconds to snooze for
+const SNOOZES: [(&str, i64); 3] = [("10m", 600), ("1h", 3600), ("1d", 86400)];
+
+/// Buttons attached to a delivered reminder, for its occurrence due at `due`.
+/// Snoozing sends a copy by DM later, which for an `early` reminder keeps the deadline.
+/// Done is recorded for the occurrence, and shown on the message.
+pub(crate) fn reminder_buttons(id: ReminderId, due: Timestamp, early: bool) -> CreateActionRow {
+    let deadline = match early {
+        true => format!(":{}", due.unix_timestamp()),
+        false => String::new(),
+    };
+    let mut buttons = SNOOZES
+        .iter()
+        .map(|(label, seconds)| {
+            CreateButton::new(format!("{}:snooze:{}{}", PREFIX, seconds, deadline))
+                .label(format!("Snooze {}", label))
+                .style(ButtonStyle::Secondary)
+        })
+        .collect::<Vec<_>>();
+    buttons.push(
+        CreateButton::new(format!("{}:done:{}:{}", PREFIX, id, due.unix_timestamp()))
+            .label("Done")
+            .style(ButtonStyle::Success),
+    );
+    CreateActionRow::Buttons(buttons)
+}
+
+/// Handles a press of one of the buttons from `reminder_buttons`
+pub(crate) async fn handle_button(
+    ctx: &serenity::Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+) -> Result<(), Error> {
+    let custom_id = component.data.custom_id.as_str();
+    let Some(action) = custom_id
+        .strip_prefix(PREFIX)
+        .and_then(|id| id.strip_prefix(':'))
+    else {
+        // not a reminder button
+        return Ok(());
+    };
+
+    // messages sent by older releases have buttons without the deadline or occurrence
+    let response = match action.split(':').collect::<Vec<_>>()[..] {
+        ["snooze", seconds] => {
+            parse_number(seconds).and_then(|seconds| snooze(component, data, seconds, None))
+        }
+        ["snooze", seconds, deadline] => parse_number(seconds).and_then(|seconds| {
+            let deadline = Timestamp::from_unix_timestamp(parse_number(deadline)?)?;
+            snooze(component, data, seconds, Some(deadline))
+        }),
+        ["done"] => done(component, data, None),
+        ["done", id, due] => id.parse::<ReminderId>().and_then(|id| {
+            let due = Timestamp::from_unix_timestamp(parse_number(due)?)?;
+            done(component, data, Some((id, due)))
+        }),
+        _ => Err(format!("Unknown button {}", custom_id).into()),
+    };
+    // errors are shown to the user who pressed the button
+    let response = response.unwrap_or_else(|e| {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(format!("An error occured: {}", e))
+                .ephemeral(true),
+        )
+    });
+    component.create_response(&ctx.http, response).await?;
+    Ok(())
+}
+
+fn parse_number(s: &str) -> Result<i64, Error> {
+    Ok(s.parse()?)
+}
+
+/// Sends the reminder again after a while to whoever pressed the button, as a personal reminder.
+/// Nobody else is sent it again, so role mentions are not copied.
+fn snooze(
+    component: &ComponentInteraction,
+    data: &UserData,
+    seconds: i64,
+    deadline: Option<Timestamp>,
+) -> Result<CreateInteractionResponse, Error> {
+    let key = ReminderKey::User(component.user.id);
+    let embed = component.message.embeds.first();
+
+    // the delivered message is all that is left of a one-off reminder, so copy from it
+    let name = embed
+        .and_then(|embed| embed.title.clone())
+        .map(|title| title.trim_end_matches(" (late)").to_string())
+        .filter(|title| title != "Reminder");
+    let description = embed.and_then(|embed| embed.description.clone());
+    let target_date = Timestamp::from_unix_timestamp(Timestamp::now().unix_timestamp() + seconds)?;
+    let mut reminder = Reminder::one_off(component.user.id, target_date, name, None, description);
+    reminder.deadline = deadline;
+
+    let reminder = { data.lock().unwrap().add_reminder(key, reminder)? };
+    data.scheduler.schedule(key, reminder);
+
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(format!(
+                "Snoozed, the reminder will be sent to you by DM {}.",
+                FormattedTimestamp::new(target_date, Some(FormattedTimestampStyle::RelativeTime))
+            ))
+            .ephemeral(true),
+    ))
+}
+
+/// Records that the user is done with the occurrence of the reminder, and lists everyone who is
+/// on the message. Messages from older releases have no occurrence, so only the message has the list.
+fn done(
+    component: &ComponentInteraction,
+    data: &UserData,
+    occurrence: Option<(ReminderId, Timestamp)>,
+) -> Result<CreateInteractionResponse, Error> {
+    let Some(embed) = component.message.embeds.first() else {
+        return Err("Reminder message has no embed.".into());
+    };
+
+    let acknowledged = match occurrence {
+        Some((id, due)) => data
+            .lock()
+            .unwrap()
+            .acknowledge(id, due, component.user.id)
+            .map(<[_]>::to_vec),
+        None => {
+            let mut users = embed
+                .fields
+                .iter()
+                .find(|field| field.name == DONE_FIELD)
+                .map(|field| {
+                    field
+                        .value
+                        .split_whitespace()
+                        .filter_map(parse_user_mention)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            match users.contains(&component.user.id) {
+                true => Err("You have already marked this reminder as done.".into()),
+                false => {
+                    users.push(component.user.id);
+                    Ok(users)
+                }
+            }
+        }
+    };
+    let users = match acknowledged {
+        Ok(users) => users,
+        Err(e) => {
+            return Ok(CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(e.to_string())
+                    .ephemeral(true),
+            ))
+        }
+    };
+
+    let mut new_embed = CreateEmbed::default();
+    if let Some(title) = &embed.title {
+        new_embed = new_embed.title(title);
+    }
+    if let Some(description) = &embed.description {
+        new_embed = new_embed.description(description);
+    }
+    // keep other fields, such as when an early reminder is due
+    for field in embed.fields.iter().filter(|field| field.name != DONE_FIELD) {
+        new_embed = new_embed.field(&field.name, &field.value, field.inline);
+    }
+    let mentions = users
+        .into_iter()
+        .map(|user| Mention::from(user).to_string())
+        .collect::<Vec<_>>();
+    new_embed = new_embed.field(DONE_FIELD, mentions.join(" "), false);
+
+    Ok(CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new().embed(new_embed),
+    ))
+}
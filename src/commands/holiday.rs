@@ -0,0 +1,143 @@
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+use crate::{
+    backend::data::{DateRange, Reminder, ReminderKey},
+    Context, Error,
+};
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("add", "remove", "list"),
+    subcommand_required
+)]
+pub(crate) async fn holiday(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Queues reminders again after the holidays they skip have changed
+fn reschedule(ctx: &Context<'_>, reminders: Vec<(ReminderKey, Reminder)>) {
+    let scheduler = &ctx.data().scheduler;
+    for (key, reminder) in reminders {
+        scheduler.cancel(key, reminder.id());
+        scheduler.schedule(key, reminder);
+    }
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn add(
+    ctx: Context<'_>,
+    #[description = "Holiday name, e.g. Reading Week"] name: String,
+    #[description = "First day, e.g. 2024-02-19"] start: String,
+    #[description = "Last day, if longer than one day"] end: Option<String>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let range = match end {
+        Some(end) => format!("{}..{}", start, end),
+        None => start,
+    };
+    let range = match range.parse::<DateRange>() {
+        Ok(range) => range,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let name = name.trim().to_string();
+    let changed = {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .set_holiday(guild_id, name.clone(), range)
+    };
+    reschedule(&ctx, changed);
+
+    reply = reply
+        .content(format!("Added holiday {} ({}).", name, range))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn remove(
+    ctx: Context<'_>,
+    #[description = "Holiday name (from list command)"] name: String,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let removed = {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .remove_holiday(guild_id, name.trim())
+    };
+    match removed {
+        Ok(changed) => {
+            reschedule(&ctx, changed);
+            reply = reply.content("Removed!");
+        }
+        Err(e) => {
+            reply = reply.content(e.to_string());
+        }
+    }
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only)]
+pub(crate) async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    {
+        let lock = ctx.data().lock().unwrap();
+        let holidays = lock.holidays(guild_id);
+        if holidays.is_empty() {
+            reply = reply
+                .content("No holidays have been set for this server.")
+                .ephemeral(true);
+        } else {
+            let embed = CreateEmbed::default()
+                .title("Holidays")
+                .description("Repeating reminders set to skip holidays are not sent on these days.")
+                .fields(
+                    holidays
+                        .into_iter()
+                        .map(|(name, range)| (name.clone(), range.to_string(), false)),
+                );
+            reply = reply.embed(embed);
+        }
+    }
+
+    ctx.send(reply).await?;
+    Ok(())
+}
@@ -1,50 +1,100 @@
 use std::sync::Arc;
 
 use poise::serenity_prelude::{
-    self as serenity, Cache, ChannelId, CreateEmbed, CreateMessage, FormattedTimestamp,
-    FormattedTimestampStyle, GuildId, Http, Mention,
+    self as serenity, Cache, ChannelId, ChannelType, CreateEmbed, CreateForumPost, CreateMessage,
+    CreateThread, FormattedTimestamp, FormattedTimestampStyle, GuildId, Http, Mention, Timestamp,
+    UserId,
 };
 
-use crate::{backend::data::Reminder, Context, Error};
+use crate::{
+    backend::{
+        calendar::TermDate,
+        data::{Reminder, ReminderKey},
+        datetime,
+    },
+    Context, Error,
+};
 
+pub(crate) mod buttons;
+pub(crate) mod holiday;
 pub(crate) mod reminder;
+pub(crate) mod term;
+pub(crate) mod timezone;
 
-/// Helper function to get the guild and channel ids
-pub(crate) async fn get_data(
+/// Helper function to get where a command's reminders are kept.
+/// Personal reminders belong to the user, as do any made in DMs.
+pub(crate) async fn get_key(
     ctx: &Context<'_>,
     channel: Option<serenity::GuildChannel>,
-) -> Result<(GuildId, ChannelId), Error> {
-    let (guild_id, channel_id) = match channel {
-        Some(channel) => (channel.guild_id, channel.id),
-        None => match ctx.guild_id() {
-            Some(guild_id) => (guild_id, ctx.channel_id()),
-            None => {
-                return Err("This command is only available in servers!".into());
-            }
+    personal: bool,
+) -> Result<ReminderKey, Error> {
+    let key = match (channel, personal) {
+        (Some(_), true) => {
+            return Err("Personal reminders are sent by DM, so cannot have a channel.".into());
+        }
+        (Some(channel), false) => ReminderKey::Channel(channel.guild_id, channel.id),
+        (None, true) => ReminderKey::User(ctx.author().id),
+        (None, false) => match ctx.guild_id() {
+            Some(guild_id) => ReminderKey::Channel(guild_id, ctx.channel_id()),
+            None => ReminderKey::User(ctx.author().id),
         },
     };
 
-    Ok((guild_id, channel_id))
+    Ok(key)
+}
+
+/// Parses a date given to a command, in the time zone of the user.
+/// Accepts a Unix timestamp, a term date like "T1 W7 Fri 12:00" using the guild's terms,
+/// or anything `datetime::parse` understands.
+pub(crate) fn parse_datetime(
+    ctx: &Context<'_>,
+    guild_id: Option<GuildId>,
+    input: &str,
+) -> Result<Timestamp, Error> {
+    let input = input.trim();
+    if let Ok(unix) = input.parse::<i64>() {
+        return Ok(Timestamp::from_unix_timestamp(unix)?);
+    }
+
+    let lock = ctx.data().lock().unwrap();
+    let time_zone = lock.time_zone(guild_id, ctx.author().id);
+    let datetime = match input.parse::<TermDate>() {
+        Ok(date) => {
+            let Some(calendar) = guild_id.and_then(|guild_id| lock.calendar(guild_id)) else {
+                return Err("No terms have been set for this server, see /term add.".into());
+            };
+            datetime::from_local(&time_zone, calendar.resolve(&date)?)?
+        }
+        Err(_) => datetime::parse(input, &chrono::Utc::now().with_timezone(&time_zone))?,
+    };
+    Ok(Timestamp::from_unix_timestamp(datetime.timestamp())?)
 }
 
 /// Creates and sends the message for a reminder.
+/// Personal reminders are sent by DM to each of their recipients,
+/// and subscribers to channel reminders get a copy by DM, even if posting in the channel fails.
+/// Forum channels get a new post each time, and other channels a thread if the reminder asks.
+/// Early reminders are sent with the deadline they are for, as are snoozed copies of them.
 pub(crate) async fn send_reminder(
-    channel_id: ChannelId,
+    key: ReminderKey,
     cache_http: (&Arc<Cache>, &Http),
     reminder: &Reminder,
+    subscribers: &[UserId],
+    late: bool,
+    deadline: Option<Timestamp>,
 ) -> Result<(), Error> {
-    let guild_channel = match channel_id.to_channel(cache_http).await?.guild() {
-        Some(guild_channel) => guild_channel,
-        None => return Err("Failed to find channel for reminder!".into()),
-    };
-
     let mut reply = CreateMessage::default();
     let mut embed = CreateEmbed::default();
+    let deadline = deadline.or(reminder.deadline);
 
-    let title = match reminder.name.clone() {
+    let mut title = match reminder.name.clone() {
         Some(title) => title,
         None => "Reminder".to_string(),
     };
+    // sent after the bot was offline when it was due
+    if late {
+        title += " (late)";
+    }
 
     let roles = match &reminder.roles {
         Some(roles) => {
@@ -75,8 +125,113 @@ pub(crate) async fn send_reminder(
         embed = embed.description(text);
     }
 
-    reply = reply.content(roles).embed(embed);
+    if let Some(deadline) = deadline {
+        embed = embed.field(
+            "Due",
+            format!(
+                "{} ({})",
+                FormattedTimestamp::new(deadline, Some(FormattedTimestampStyle::RelativeTime)),
+                FormattedTimestamp::new(deadline, Some(FormattedTimestampStyle::LongDateTime))
+            ),
+            false,
+        );
+    }
+
+    reply = reply
+        .content(roles)
+        .embed(embed)
+        .components(vec![buttons::reminder_buttons(
+            reminder.id(),
+            deadline.unwrap_or_else(|| reminder.next_due()),
+            deadline.is_some(),
+        )]);
 
-    guild_channel.send_message(cache_http, reply).await?;
+    match key {
+        ReminderKey::Channel(_, channel_id) => {
+            // subscribers still get their copy if the channel cannot be posted in
+            let posted = post_in_channel(cache_http, channel_id, reminder, deadline, &reply).await;
+            // role pings do nothing in DMs, so say where the copy is from instead
+            let copy = reply.content(format!("Sent in {}", Mention::from(channel_id)));
+            let dms = send_dms(cache_http, subscribers, &copy).await;
+            match (posted, dms) {
+                (Err(e), Err(dm_error)) => return Err(format!("{}. {}", e, dm_error).into()),
+                (Err(e), _) | (_, Err(e)) => return Err(e),
+                _ => {}
+            }
+        }
+        ReminderKey::User(_) => send_dms(cache_http, &reminder.recipients(), &reply).await?,
+    }
+    Ok(())
+}
+
+/// Posts a reminder in its channel, as a forum post or a message with an optional thread
+async fn post_in_channel(
+    cache_http: (&Arc<Cache>, &Http),
+    channel_id: ChannelId,
+    reminder: &Reminder,
+    deadline: Option<Timestamp>,
+    reply: &CreateMessage,
+) -> Result<(), Error> {
+    let guild_channel = match channel_id.to_channel(cache_http).await?.guild() {
+        Some(guild_channel) => guild_channel,
+        None => return Err("Failed to find channel for reminder!".into()),
+    };
+    if guild_channel.kind == ChannelType::Forum {
+        // forums only take messages as the start of a post
+        let post = CreateForumPost::new(thread_name(reminder), reply.clone());
+        guild_channel.create_forum_post(cache_http, post).await?;
+    } else {
+        let message = guild_channel
+            .send_message(cache_http, reply.clone())
+            .await?;
+        // early reminders go without, so each occurrence has one thread.
+        // threads cannot have threads of their own
+        if reminder.thread && deadline.is_none() && guild_channel.thread_metadata.is_none() {
+            let thread = CreateThread::new(thread_name(reminder));
+            guild_channel
+                .create_thread_from_message(cache_http, message.id, thread)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Name of a thread opened for a reminder, with the local date it was sent on
+fn thread_name(reminder: &Reminder) -> String {
+    let time_zone = reminder
+        .repeating
+        .as_ref()
+        .and_then(|repeat| repeat.time_zone)
+        .unwrap_or(chrono_tz::UTC);
+    let title = match &reminder.name {
+        // thread names can be at most 100 characters long
+        Some(name) => name.chars().take(80).collect(),
+        None => "Reminder".to_string(),
+    };
+    format!(
+        "{} ({})",
+        title,
+        chrono::Utc::now()
+            .with_timezone(&time_zone)
+            .format("%Y-%m-%d")
+    )
+}
+
+/// Sends a message to each user by DM.
+/// One user with DMs closed does not stop the others getting it.
+async fn send_dms(
+    cache_http: (&Arc<Cache>, &Http),
+    users: &[UserId],
+    message: &CreateMessage,
+) -> Result<(), Error> {
+    let mut failed = Vec::new();
+    for user_id in users {
+        if let Err(e) = user_id.direct_message(cache_http, message.clone()).await {
+            failed.push(format!("User {}: {}", user_id, e));
+        }
+    }
+    if !failed.is_empty() {
+        return Err(format!("Failed to send DMs, {}", failed.join(", ")).into());
+    }
     Ok(())
 }
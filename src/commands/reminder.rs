@@ -1,156 +1,71 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-    time::{Duration, SystemTime},
-};
+use std::collections::{HashMap, HashSet};
 
 use poise::{
     serenity_prelude::{
-        self as serenity, parse_role_mention, Cache, ChannelId, CreateEmbed, FormattedTimestamp,
-        FormattedTimestampStyle, GuildId, Http, Mention, RoleId, Timestamp,
+        self as serenity, parse_role_mention, parse_user_mention, ChannelId, ChannelType,
+        CreateEmbed, FormattedTimestamp, FormattedTimestampStyle, Mention, RoleId, Timestamp,
+        UserId,
     },
     CreateReply,
 };
-use tokio::task::JoinHandle;
 
 use crate::{
-    backend::data::{Interval, Reminder, ReminderTable, Repeat},
-    commands::{get_data, send_reminder},
+    backend::{
+        data::{DateRange, MissedPolicy, Reminder, ReminderId, ReminderKey, Repeat},
+        datetime,
+        export::{ExportFormat, GuildExport, MAX_IMPORT_BYTES},
+        store::ReminderFilter,
+    },
+    commands::{get_key, parse_datetime},
     Context, Error,
 };
 
-// Creates an async task to send a reminder at the correct time.
-// Implicitly stores the task handle for the created task in the `task` parameter.
-// awful way of doing this but I cannot think of any better way without unsafe
-async fn schedule_reminder_message(
-    guild_id: GuildId,
-    channel_id: ChannelId,
-    cache_http: (Arc<Cache>, Arc<Http>),
-    reminder: Reminder,
-    reminders: Arc<Mutex<ReminderTable>>,
-    tasks: Arc<Mutex<HashMap<Reminder, JoinHandle<()>>>>,
-) -> Result<(), Error> {
-    // destructure cache_http for cloning later
-    let (cache, http) = cache_http;
-    let target = reminder.target_date;
-    // if the reminder is repeating, it is possible this is not the first time it is ran.
-    // if so, use the timestamp from its next method instead
-    let timestamp = match reminder.repeating {
-        Some(repeat) => repeat.next(&target).timestamp(),
-        None => target.timestamp(),
-    };
-
-    // hacky way of getting a duration from a unix timestamp
-    let sleep_duration = (SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp as u64))
-        .duration_since(SystemTime::now())?;
-
-    // cloning for use in the async move block
-    let reminder_clone = reminder.clone();
-    let tasks_clone = tasks.clone();
-
-    let handle = tokio::spawn(async move {
-        tokio::time::sleep(sleep_duration).await;
-        let _ = send_reminder(
-            channel_id,
-            (&(cache.clone()), &(http.clone())),
-            &reminder_clone,
-        )
-        .await;
-
-        // for repeating reminders, reminder needs to be updated and a new task needs to be spawned
-        match reminder_clone.repeating {
-            Some(mut repeat) => {
-                // cloning the whole hashset for a single if-statement. not good.
-                let reminders_locked = {
-                    let mut r = reminders.lock().unwrap();
-                    r.get_reminders_mut(guild_id, channel_id).cloned()
-                };
-
-                // re-create reminder with an increased repeat count
-                let new_reminder = {
-                    let mut r = reminder_clone.clone();
-                    repeat.increment_index();
-                    r.repeating = Some(repeat);
-                    r
-                };
-
-                if reminders_locked.is_some() {
-                    {
-                        let mut lock = reminders.lock().unwrap();
-                        let reminders_set = lock.get_reminders_mut(guild_id, channel_id).unwrap();
-                        // replace old reminder with one with higher repeat count
-                        reminders_set.replace(new_reminder.clone());
-                    }
-
-                    // https://github.com/rust-lang/rust/issues/78649#issuecomment-1264353351
-                    // recursive aync is not allowed in rust. so I used the workaround above
-                    #[inline(always)]
-                    fn recurse_schedule(
-                        guild_id: GuildId,
-                        channel_id: ChannelId,
-                        cache: Arc<Cache>,
-                        http: Arc<Http>,
-                        new_reminder: Reminder,
-                        reminders: Arc<Mutex<ReminderTable>>,
-                        tasks_clone: Arc<Mutex<HashMap<Reminder, JoinHandle<()>>>>,
-                    ) -> poise::BoxFuture<'static, Result<(), Error>> {
-                        Box::pin(schedule_reminder_message(
-                            guild_id,
-                            channel_id,
-                            (cache, http),
-                            new_reminder,
-                            reminders,
-                            tasks_clone,
-                        ))
-                    }
-                    let _ = recurse_schedule(
-                        guild_id,
-                        channel_id,
-                        cache.clone(),
-                        http.clone(),
-                        new_reminder,
-                        reminders.clone(),
-                        tasks_clone,
-                    )
-                    .await;
-                }
-            }
-            None => {
-                let mut reminders = reminders.lock().unwrap();
-                // if the reminder doesn't repeat it can be removed after its done
-                let _ = reminders.remove_reminder(guild_id, channel_id, &reminder_clone);
-            }
-        };
-    });
-
-    {
-        tasks.lock().unwrap().insert(reminder, handle);
-    }
-    Ok(())
-}
-
 #[poise::command(
     slash_command,
-    subcommands("add", "remove", "list", "info"),
+    subcommands(
+        "add",
+        "edit",
+        "remove",
+        "list",
+        "info",
+        "mine",
+        "subscribe",
+        "unsubscribe",
+        "subscriptions",
+        "missed",
+        "export",
+        "import"
+    ),
     subcommand_required
 )]
 pub(crate) async fn reminder(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[poise::command(slash_command)]
 pub(crate) async fn add(
     ctx: Context<'_>,
+    #[description = "When, e.g. tomorrow 9am, in 3 days, 2024-03-01 12:00, T1 W7 Fri"]
+    datetime: String,
+    #[description = "Repeat, e.g. weekly, every 2 weeks, cron or RRULE"] repeat: Option<String>,
+    #[description = "Stop repeating after this date"] until: Option<String>,
     #[min = 1]
-    #[description = "Unix Timestamp"]
-    datetime: i64,
-    #[description = "Repeat interval"] interval: Option<Interval>,
+    #[description = "Stop repeating after this many reminders"]
+    times: Option<u32>,
+    #[description = "Dates to skip, e.g. 2024-02-19..2024-02-23, 2024-03-01"] skip: Option<String>,
+    #[description = "Skip the server's holidays"] holidays: Option<bool>,
+    #[description = "Also remind before, e.g. 1w, 1d, 1h"] before: Option<String>,
     #[description = "Reminder name"] name: Option<String>,
     #[description = "Reminder text"] text: Option<String>,
     #[description = "Target Channel"]
-    #[channel_types("Text")]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
     channel: Option<serenity::GuildChannel>,
     #[description = "Space-separated list of roles to be mentioned."] roles: Option<String>,
+    #[description = "What to do if this is missed"] missed: Option<MissedPolicy>,
+    #[description = "Send to you by DM instead of a channel"] personal: Option<bool>,
+    #[description = "Space-separated server members to DM instead of you"] users: Option<String>,
+    #[description = "Open a thread each time it is sent"] thread: Option<bool>,
 ) -> Result<(), Error> {
     let mut reply = CreateReply::default();
 
@@ -160,25 +75,73 @@ pub(crate) async fn add(
             .filter_map(parse_role_mention)
             .collect::<Vec<RoleId>>()
     });
+    let users = users.map(|users| {
+        users
+            .split_whitespace()
+            .filter_map(parse_user_mention)
+            .collect::<Vec<UserId>>()
+    });
 
-    if datetime <= chrono::Utc::now().timestamp() {
+    // maximum character count in a embed description is 4096
+    if text.clone().is_some_and(|s| s.chars().count() > 4096) {
         reply = reply
-            .content("Timestamp must be in the future!")
+            .content("The reminder text body is must be less than 4096 characters long!")
             .ephemeral(true);
         ctx.send(reply).await?;
         return Ok(());
     }
 
-    // maximum character count in a embed description is 4096
-    if text.clone().is_some_and(|s| s.chars().count() > 4096) {
+    if thread == Some(true) {
+        if let Err(e) = check_thread(channel.as_ref()) {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    }
+
+    // choosing who to DM makes the reminder personal
+    let personal = personal.unwrap_or(false) || users.is_some();
+    let key = match get_key(&ctx, channel, personal).await {
+        Ok(key) => key,
+        Err(error) => {
+            reply = reply
+                .content(format!("An error occured: {}", error))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+    // dates and time zones are those of the server the command is used in,
+    // even for personal reminders which are not kept under it
+    let guild_id = ctx.guild_id();
+
+    // others can only be sent reminders by someone in a server with them
+    if let Some(users) = &users {
+        if let Err(e) = check_members(&ctx, guild_id, users).await {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    }
+
+    // nobody would see role pings in DMs
+    if matches!(key, ReminderKey::User(_)) && roles.as_ref().is_some_and(|roles| !roles.is_empty())
+    {
         reply = reply
-            .content("The reminder text body is must be less than 4096 characters long!")
+            .content("Personal reminders cannot mention roles.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    if matches!(key, ReminderKey::User(_)) && thread == Some(true) {
+        reply = reply
+            .content("Personal reminders cannot open threads.")
             .ephemeral(true);
         ctx.send(reply).await?;
         return Ok(());
     }
 
-    let datetime = match Timestamp::from_unix_timestamp(datetime) {
+    let datetime = match parse_datetime(&ctx, guild_id, &datetime) {
         Ok(datetime) => datetime,
         Err(e) => {
             reply = reply
@@ -189,8 +152,243 @@ pub(crate) async fn add(
         }
     };
 
-    let (guild_id, channel_id) = match get_data(&ctx, channel).await {
-        Ok((guild_id, channel_id)) => (guild_id, channel_id),
+    if datetime <= Timestamp::now() {
+        reply = reply
+            .content("Timestamp must be in the future!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    // parse Repeat from text
+    let mut repeat = match repeat.map(|repeat| repeat.parse::<Repeat>()).transpose() {
+        Ok(repeat) => repeat,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let skipped = match skip.as_deref().map(DateRange::parse_list).transpose() {
+        Ok(skipped) => skipped,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    // limits and skipped days only make sense for repeating reminders
+    if let Some(repeat) = &mut repeat {
+        // repeats keep to the local time of whoever set them
+        repeat.time_zone = Some(
+            ctx.data()
+                .lock()
+                .unwrap()
+                .time_zone(guild_id, ctx.author().id),
+        );
+        repeat.max_occurrences = times;
+        repeat.skipped = skipped.unwrap_or_default();
+        repeat.skip_holidays = holidays.unwrap_or(false);
+        repeat.until = match until
+            .map(|until| parse_datetime(&ctx, guild_id, &until))
+            .transpose()
+        {
+            Ok(Some(until)) if until <= datetime => {
+                reply = reply
+                    .content("The end of the repeat must be after the reminder timestamp!")
+                    .ephemeral(true);
+                ctx.send(reply).await?;
+                return Ok(());
+            }
+            Ok(until) => until,
+            Err(e) => {
+                reply = reply
+                    .content(format!("Invalid timestamp provided: {}", e))
+                    .ephemeral(true);
+                ctx.send(reply).await?;
+                return Ok(());
+            }
+        };
+    } else if until.is_some() || times.is_some() || skipped.is_some() || holidays == Some(true) {
+        reply = reply
+            .content("End dates, limits and skipped days can only be set for repeating reminders.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let lead_times = match before
+        .as_deref()
+        .map(Reminder::parse_lead_times)
+        .transpose()
+    {
+        Ok(lead_times) => lead_times.unwrap_or_default(),
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data();
+
+    // create reminder and schedule it
+    let mut reminder = Reminder::from_context(&ctx, datetime, repeat, name, roles, text);
+    reminder.missed = missed;
+    reminder.lead_times = lead_times;
+    reminder.recipients = users.unwrap_or_default();
+    reminder.thread = thread.unwrap_or(false);
+    let checked = match &reminder.repeating {
+        Some(repeat) => repeat.check_sent(&reminder.target_date),
+        None => Ok(()),
+    };
+    if let Err(e) = checked.and_then(|_| reminder.check_lead_times()) {
+        reply = reply.content(e.to_string()).ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    let added = { data.lock().unwrap().add_reminder(key, reminder) };
+    match added {
+        Ok(reminder) => {
+            // repeats like crons are first sent at their first match, not at the timestamp
+            let mut content = format!(
+                "Added! Reminder id: `{}`, set for {} ({})",
+                reminder.id(),
+                FormattedTimestamp::new(
+                    reminder.next_due(),
+                    Some(FormattedTimestampStyle::LongDateTime)
+                ),
+                FormattedTimestamp::new(
+                    reminder.next_due(),
+                    Some(FormattedTimestampStyle::RelativeTime)
+                )
+            );
+            // preview when a repeating reminder will be sent
+            if let Some(repeat) = &reminder.repeating {
+                content += "\nNext times:";
+                for timestamp in repeat.upcoming(&reminder.target_date, 5) {
+                    content += &format!(
+                        "\n- {}",
+                        FormattedTimestamp::new(
+                            timestamp,
+                            Some(FormattedTimestampStyle::LongDateTime)
+                        )
+                    );
+                }
+            }
+            reply = reply.content(content);
+            data.scheduler.schedule(key, reminder);
+        }
+        Err(e) => {
+            reply = reply.content(format!("An error occured: {}", e));
+        }
+    }
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Checks a reminder sent to a channel can open threads there
+fn check_thread(channel: Option<&serenity::GuildChannel>) -> Result<(), Error> {
+    match channel {
+        Some(channel) if channel.thread_metadata.is_some() => {
+            Err("Reminders sent in a thread cannot open threads of their own.".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Where a command's reminders are, for use in replies
+fn place(key: ReminderKey) -> &'static str {
+    match key {
+        ReminderKey::Channel(..) => "this channel",
+        ReminderKey::User(_) => "your DMs",
+    }
+}
+
+/// Names the channel of a command's reminders, for use in titles
+/// Checks that the users a personal reminder is sent to are all members of the server
+async fn check_members(
+    ctx: &Context<'_>,
+    guild_id: Option<serenity::GuildId>,
+    users: &[UserId],
+) -> Result<(), Error> {
+    let Some(guild_id) = guild_id else {
+        return Err("Reminders for other users can only be set in a server.".into());
+    };
+    for &user_id in users {
+        if guild_id.member(ctx, user_id).await.is_err() {
+            return Err(
+                format!("{} is not a member of this server.", Mention::from(user_id)).into(),
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn describe_key(ctx: &Context<'_>, key: ReminderKey) -> Result<String, Error> {
+    match key {
+        ReminderKey::Channel(_, channel_id) => {
+            let cache = serenity::CacheHttp::cache(ctx).unwrap();
+            let http = serenity::CacheHttp::http(ctx);
+            let channel_name = channel_id.to_channel((cache, http)).await?;
+            Ok(format!("channel {}", channel_name))
+        }
+        ReminderKey::User(_) => Ok("your DMs".to_string()),
+    }
+}
+
+/// Clears an optional field when given instead of a value
+fn is_none(value: &str) -> bool {
+    value.trim().eq_ignore_ascii_case("none")
+}
+
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command)]
+pub(crate) async fn edit(
+    ctx: Context<'_>,
+    #[description = "Reminder id (from list command)"] id: String,
+    #[description = "New time, e.g. tomorrow 9am or 2024-03-01 12:00"] datetime: Option<String>,
+    #[description = "New repeat, or none to stop repeating"] repeat: Option<String>,
+    #[description = "Stop repeating after this date, or none"] until: Option<String>,
+    #[description = "Stop repeating after this many reminders, 0 for no limit"] times: Option<u32>,
+    #[description = "Dates to skip, or none"] skip: Option<String>,
+    #[description = "Skip the server's holidays"] holidays: Option<bool>,
+    #[description = "Also remind before, e.g. 1w, 1d, 1h, or none"] before: Option<String>,
+    #[description = "Reminder name"] name: Option<String>,
+    #[description = "Reminder text"] text: Option<String>,
+    #[description = "Space-separated list of roles to be mentioned, or none"] roles: Option<String>,
+    #[description = "What to do if this is missed"] missed: Option<MissedPolicy>,
+    #[description = "Target Channel"]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Edit one of your personal reminders"] personal: Option<bool>,
+    #[description = "Open a thread each time it is sent"] thread: Option<bool>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let id = match id.parse::<ReminderId>() {
+        Ok(id) => id,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    if thread == Some(true) {
+        if let Err(e) = check_thread(channel.as_ref()) {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    }
+
+    let key = match get_key(&ctx, channel, personal.unwrap_or(false)).await {
+        Ok(key) => key,
         Err(error) => {
             reply = reply
                 .content(format!("An error occured: {}", error))
@@ -200,40 +398,304 @@ pub(crate) async fn add(
         }
     };
 
-    // create Repeat from Interval
-    let mut repeat = None;
-    if let Some(interval) = interval {
-        repeat = Some(Repeat::new(interval));
+    // maximum character count in a embed description is 4096
+    if text.clone().is_some_and(|s| s.chars().count() > 4096) {
+        reply = reply
+            .content("The reminder text body is must be less than 4096 characters long!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
     }
 
-    let data = ctx.data();
-    let http = data.http.clone();
-    let cache = data.cache.clone();
+    let old = { ctx.data().lock().unwrap().get_reminder(key, id).cloned() };
+    let Some(old) = old else {
+        reply = reply
+            .content(format!(
+                "Reminder id {} was not found in {}.",
+                id,
+                place(key)
+            ))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
 
-    // create reminder and schedule it
-    let reminder = Reminder::from_context(&ctx, datetime, repeat, name, roles, text);
-    {
-        if let Err(e) = data
-            .lock()
-            .unwrap()
-            .add_reminder(guild_id, channel_id, reminder.clone())
-        {
+    let edits = TimingEdits {
+        datetime,
+        repeat,
+        until,
+        times,
+        skip,
+        holidays,
+    };
+    let mut reminder = match edit_reminder(&ctx, ctx.guild_id(), old.clone(), edits) {
+        Ok(reminder) => reminder,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(name) = name {
+        reminder.name = Some(name).filter(|name| !name.is_empty());
+    }
+    if let Some(text) = text {
+        reminder.description = Some(text).filter(|text| !text.is_empty());
+    }
+    if let Some(roles) = roles {
+        let roles = roles
+            .split_whitespace()
+            .filter_map(parse_role_mention)
+            .collect::<Vec<RoleId>>();
+        if matches!(key, ReminderKey::User(_)) && !roles.is_empty() {
+            reply = reply
+                .content("Personal reminders cannot mention roles.")
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+        reminder.roles = Some(roles).filter(|roles| !roles.is_empty());
+    }
+    if missed.is_some() {
+        reminder.missed = missed;
+    }
+    if let Some(thread) = thread {
+        if matches!(key, ReminderKey::User(_)) && thread {
+            reply = reply
+                .content("Personal reminders cannot open threads.")
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+        reminder.thread = thread;
+    }
+    if let Some(before) = before {
+        reminder.lead_times = match is_none(&before) {
+            true => Vec::new(),
+            false => match Reminder::parse_lead_times(&before) {
+                Ok(lead_times) => lead_times,
+                Err(e) => {
+                    reply = reply.content(e.to_string()).ephemeral(true);
+                    ctx.send(reply).await?;
+                    return Ok(());
+                }
+            },
+        };
+    }
+
+    // a new repeat can be too close together for the early reminders kept from before
+    if let Err(e) = reminder.check_lead_times() {
+        reply = reply.content(e.to_string()).ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let rescheduled = reminder.target_date != old.target_date
+        || reminder.repeating != old.repeating
+        || reminder.lead_times != old.lead_times;
+    let updated = { ctx.data().lock().unwrap().update_reminder(key, reminder) };
+    match updated {
+        Ok(reminder) => {
+            let next = match &reminder.repeating {
+                Some(repeat) => repeat.next(&reminder.target_date),
+                None => reminder.target_date,
+            };
+            reply = reply.content(format!(
+                "Updated reminder `{}`, next due {}.",
+                id,
+                FormattedTimestamp::new(next, Some(FormattedTimestampStyle::LongDateTime))
+            ));
+            // the queued delivery is for the old time
+            if rescheduled {
+                let scheduler = &ctx.data().scheduler;
+                scheduler.cancel(key, id);
+                scheduler.schedule(key, reminder);
+            }
+        }
+        Err(e) => {
+            reply = reply.content(format!("An error occured: {}", e));
+        }
+    }
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Options of the edit command that change when a reminder is sent
+struct TimingEdits {
+    datetime: Option<String>,
+    repeat: Option<String>,
+    until: Option<String>,
+    times: Option<u32>,
+    skip: Option<String>,
+    holidays: Option<bool>,
+}
+
+/// Applies the timing options of the edit command, reading dates in the server it is used in
+fn edit_reminder(
+    ctx: &Context<'_>,
+    guild_id: Option<serenity::GuildId>,
+    mut reminder: Reminder,
+    edits: TimingEdits,
+) -> Result<Reminder, Error> {
+    let TimingEdits {
+        datetime,
+        repeat,
+        until,
+        times,
+        skip,
+        holidays,
+    } = edits;
+    let now = Timestamp::now();
+    let next_due = reminder.next_due();
+
+    let moved = datetime.is_some();
+    if let Some(datetime) = datetime {
+        let datetime = parse_datetime(ctx, guild_id, &datetime)?;
+        if datetime <= now {
+            return Err("Timestamp must be in the future!".into());
+        }
+        reminder.target_date = datetime;
+    }
+
+    let recurrence_changed = repeat.is_some();
+    if let Some(repeat) = repeat {
+        reminder.repeating = match is_none(&repeat) {
+            // the timestamp is where the repeat started, so keep the time it was next due instead
+            true if !moved && reminder.repeating.is_some() => {
+                if next_due <= now {
+                    return Err("The repeat is overdue, give a new timestamp as well.".into());
+                }
+                reminder.target_date = next_due;
+                None
+            }
+            true => None,
+            false => {
+                let mut new_repeat = repeat.parse::<Repeat>()?;
+                match &reminder.repeating {
+                    Some(old) => new_repeat.keep_settings(old),
+                    None => {
+                        new_repeat.time_zone = Some(
+                            ctx.data()
+                                .lock()
+                                .unwrap()
+                                .time_zone(guild_id, ctx.author().id),
+                        );
+                    }
+                }
+                Some(new_repeat)
+            }
+        };
+    }
+
+    let target_date = reminder.target_date;
+    let Some(repeat) = reminder.repeating.as_mut() else {
+        if until.is_some() || times.is_some() || skip.is_some() || holidays.is_some() {
+            return Err(
+                "End dates, limits and skipped days can only be set for repeating reminders."
+                    .into(),
+            );
+        }
+        return Ok(reminder);
+    };
+
+    if let Some(until) = until {
+        repeat.until = match is_none(&until) {
+            true => None,
+            false => Some(parse_datetime(ctx, guild_id, &until)?),
+        };
+        if repeat.until.is_some_and(|until| until <= target_date) {
+            return Err("The end of the repeat must be after the reminder timestamp!".into());
+        }
+    }
+    if let Some(times) = times {
+        if times > 0 && times <= repeat.sent() {
+            return Err(format!(
+                "The reminder has already been sent {} times.",
+                repeat.sent()
+            )
+            .into());
+        }
+        repeat.max_occurrences = Some(times).filter(|&times| times > 0);
+    }
+    if let Some(skip) = skip {
+        repeat.skipped = match is_none(&skip) {
+            true => Vec::new(),
+            false => DateRange::parse_list(&skip)?,
+        };
+    }
+    if let Some(holidays) = holidays {
+        repeat.skip_holidays = holidays;
+    }
+
+    // repeats are counted from the reminder's timestamp, which may be in the past
+    if moved || recurrence_changed {
+        repeat.restart();
+        if !repeat.skip_past(&target_date, &now) {
+            return Err("This reminder would not repeat after the current time.".into());
+        }
+    }
+    if repeat.is_finished(&target_date) {
+        return Err("The repeat would have no times left after this change.".into());
+    }
+    repeat.check_sent(&target_date)?;
+    Ok(reminder)
+}
+
+/// Embeds can have at most 25 fields
+const MAX_FIELDS: usize = 25;
+
+#[poise::command(slash_command)]
+pub(crate) async fn mine(ctx: Context<'_>) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    // reminders you set in this server, or everywhere when used in DMs
+    let filter = ReminderFilter {
+        guild_id: ctx.guild_id(),
+        created_by: Some(ctx.author().id),
+        ..Default::default()
+    };
+    let table = ctx.data().data.clone();
+    let reminders = match tokio::task::spawn_blocking(move || table.query(&filter)).await? {
+        Ok(reminders) => reminders,
+        Err(e) => {
             reply = reply
                 .content(format!("An error occured: {}", e))
                 .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
         }
-        let _ = schedule_reminder_message(
-            guild_id,
-            channel_id,
-            (cache, http),
-            reminder,
-            data.data.clone(),
-            data.tasks.clone(),
-        )
-        .await;
+    };
+
+    if reminders.is_empty() {
+        reply = reply.content("You have not set any reminders here.");
+    } else {
+        let mut description = format!("Reminders: {}", reminders.len());
+        if reminders.len() > MAX_FIELDS {
+            description += &format!(", showing the first {}", MAX_FIELDS);
+        }
+        let embed = CreateEmbed::default()
+            .title("Reminders set by you")
+            .description(description)
+            .fields(reminders.iter().take(MAX_FIELDS).map(|(key, reminder)| {
+                let title = match &reminder.name {
+                    Some(name) => format!("`{}` ({})", reminder.id(), name),
+                    None => format!("`{}`", reminder.id()),
+                };
+                let location = match key {
+                    ReminderKey::Channel(_, channel_id) => {
+                        format!("In {}", Mention::from(*channel_id))
+                    }
+                    ReminderKey::User(_) => "By DM".to_string(),
+                };
+                (title, format!("{}{}", reminder, location), false)
+            }));
+        reply = reply.embed(embed);
     }
 
-    reply = reply.content("Added!").ephemeral(true);
+    reply = reply.ephemeral(true);
     ctx.send(reply).await?;
     Ok(())
 }
@@ -242,14 +704,15 @@ pub(crate) async fn add(
 pub(crate) async fn list(
     ctx: Context<'_>,
     #[description = "Target Channel"]
-    #[channel_types("Text")]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
     channel: Option<serenity::GuildChannel>,
+    #[description = "Use your personal reminders"] personal: Option<bool>,
 ) -> Result<(), Error> {
     let mut reply = CreateReply::default();
     let mut embed = CreateEmbed::default();
 
-    let (guild_id, channel_id) = match get_data(&ctx, channel).await {
-        Ok((guild_id, channel_id)) => (guild_id, channel_id),
+    let key = match get_key(&ctx, channel, personal.unwrap_or(false)).await {
+        Ok(key) => key,
         Err(error) => {
             reply = reply
                 .content(format!("An error occured: {}", error))
@@ -259,26 +722,24 @@ pub(crate) async fn list(
         }
     };
 
-    let cache = serenity::CacheHttp::cache(&ctx).unwrap();
-    let http = serenity::CacheHttp::http(&ctx);
-    let channel_name = channel_id.to_channel((cache, http)).await?;
+    let location = describe_key(&ctx, key).await?;
 
     let data = ctx.data();
     {
-        let mut lock = data.lock().unwrap();
-        match lock.get_reminders(guild_id, channel_id) {
+        let lock = data.lock().unwrap();
+        match lock.get_reminders(key) {
             None => {
-                reply = reply.content(format!("No reminders set for channel {}", channel_name));
+                reply = reply.content(format!("No reminders set for {}", location));
                 reply = reply.ephemeral(true);
             }
             Some(reminders) => {
                 embed = embed
-                    .title(format!("Reminders set for channel {}", channel_name))
+                    .title(format!("Reminders set for {}", location))
                     .description(format!("Reminders: {}", reminders.len()))
-                    .fields(reminders.iter().enumerate().map(|(n, v)| {
+                    .fields(reminders.iter().map(|v| {
                         let title = match &v.name {
-                            Some(name) => format!("{} ({})", n + 1, name),
-                            None => (n + 1).to_string(),
+                            Some(name) => format!("`{}` ({})", v.id(), name),
+                            None => format!("`{}`", v.id()),
                         };
 
                         (title, format!("{}", v), false)
@@ -295,23 +756,25 @@ pub(crate) async fn list(
 #[poise::command(slash_command)]
 pub(crate) async fn remove(
     ctx: Context<'_>,
-    #[description = "Reminder number (from list command)"] id: u16,
+    #[description = "Reminder id (from list command)"] id: String,
     #[description = "Target Channel"]
-    #[channel_types("Text")]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
     channel: Option<serenity::GuildChannel>,
+    #[description = "Use your personal reminders"] personal: Option<bool>,
 ) -> Result<(), Error> {
     let mut reply = CreateReply::default();
 
-    // The reminders list command starts at 1
-    if id < 1 {
-        reply = reply.content("Reminder id must be 1 or greater.");
-        reply = reply.ephemeral(true);
-        ctx.send(reply).await?;
-        return Ok(());
-    }
+    let id = match id.parse::<ReminderId>() {
+        Ok(id) => id,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
 
-    let (guild_id, channel_id) = match get_data(&ctx, channel).await {
-        Ok((guild_id, channel_id)) => (guild_id, channel_id),
+    let key = match get_key(&ctx, channel, personal.unwrap_or(false)).await {
+        Ok(key) => key,
         Err(error) => {
             reply = reply
                 .content(format!("An error occured: {}", error))
@@ -321,35 +784,24 @@ pub(crate) async fn remove(
         }
     };
 
-    let reminders = {
+    {
         let mut lock = ctx.data().lock().unwrap();
-        lock.get_reminders(guild_id, channel_id).cloned()
-    };
-
-    if let Some(reminders) = reminders {
-        if let Some((_, reminder)) = reminders
-            .iter()
-            .enumerate()
-            .find(|(i, _)| *i == (id - 1) as usize)
-        {
-            let mut lock = ctx.data().lock().unwrap();
-            lock.remove_reminder(guild_id, channel_id, reminder)
-                .unwrap();
-            {
-                let mut lock = ctx.data().tasks.lock().unwrap();
-                let handle = lock.remove(reminder).unwrap();
-                handle.abort();
-            }
+        if lock.get_reminders(key).is_none() {
+            reply = reply
+                .content(format!("No reminders have been set for {}.", place(key)))
+                .ephemeral(true);
+        } else if lock.remove_reminder(key, id).is_ok() {
+            ctx.data().scheduler.cancel(key, id);
             reply = reply.content("Removed!");
         } else {
             reply = reply
-                .content(format!("Reminder id {} was not found in this channel.", id))
+                .content(format!(
+                    "Reminder id {} was not found in {}.",
+                    id,
+                    place(key)
+                ))
                 .ephemeral(true);
         }
-    } else {
-        reply = reply
-            .content("No reminders have been set for this channel.")
-            .ephemeral(true);
     }
 
     ctx.send(reply).await?;
@@ -359,25 +811,27 @@ pub(crate) async fn remove(
 #[poise::command(slash_command)]
 pub(crate) async fn info(
     ctx: Context<'_>,
-    #[description = "Reminder number (from list command)"] id: u16,
+    #[description = "Reminder id (from list command)"] id: String,
     #[description = "Target Channel"]
-    #[channel_types("Text")]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
     channel: Option<serenity::GuildChannel>,
+    #[description = "Use your personal reminders"] personal: Option<bool>,
 ) -> Result<(), Error> {
     let mut reply = CreateReply::default();
 
-    // The reminders list command starts at 1
-    if id < 1 {
-        reply = reply.content("Reminder id must be 1 or greater.");
-        reply = reply.ephemeral(true);
-        ctx.send(reply).await?;
-        return Ok(());
-    }
+    let id = match id.parse::<ReminderId>() {
+        Ok(id) => id,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
 
     let mut embed = CreateEmbed::default();
 
-    let (guild_id, channel_id) = match get_data(&ctx, channel).await {
-        Ok((guild_id, channel_id)) => (guild_id, channel_id),
+    let key = match get_key(&ctx, channel, personal.unwrap_or(false)).await {
+        Ok(key) => key,
         Err(error) => {
             reply = reply
                 .content(format!("An error occured: {}", error))
@@ -390,32 +844,24 @@ pub(crate) async fn info(
 
     let cache = serenity::CacheHttp::cache(&ctx).unwrap();
     let http = serenity::CacheHttp::http(&ctx);
-    let channel_name = channel_id.to_channel((cache, http)).await?;
+    let location = describe_key(&ctx, key).await?;
 
-    // This clones the hashset for the current channel
+    // This clones the reminders for the current channel
     // Not ideal but we can't use .await otherwise
     // (needed for getting the user that created the reminder)
     let reminders = {
-        let mut lock = ctx.data().lock().unwrap();
-        lock.get_reminders(guild_id, channel_id).cloned()
+        let lock = ctx.data().lock().unwrap();
+        lock.get_reminders(key).map(<[Reminder]>::to_vec)
     };
 
-    embed = embed.title(format!(
-        "Information for reminder {} in channel {}:",
-        id, channel_name
-    ));
+    embed = embed.title(format!("Information for reminder {} in {}:", id, location));
 
     match reminders {
         None => {
-            embed = embed.description("No reminders have been set for this channel!");
+            embed = embed.description(format!("No reminders have been set for {}!", place(key)));
         }
         Some(reminders) => {
-            if let Some(reminder) = reminders
-                .iter()
-                .enumerate()
-                .find(|(i, _)| *i == (id - 1) as usize)
-            {
-                let (_, reminder) = reminder;
+            if let Some(reminder) = reminders.iter().find(|reminder| reminder.id() == id) {
                 let title = match reminder.name.clone() {
                     Some(name) => name,
                     None => "Not set".to_string(),
@@ -426,10 +872,19 @@ pub(crate) async fn info(
                 };
                 let (created_at, created_by) = reminder.get_creation();
                 let target_date = reminder.target_date;
-                let repeating = reminder.repeating;
+                let repeating = reminder.repeating.clone();
 
-                let roles = match &reminder.roles {
-                    Some(roles) => {
+                let roles = match (&reminder.roles, key) {
+                    (_, ReminderKey::User(_)) => {
+                        let mut text = String::from("Sent by DM to: ");
+                        reminder
+                            .recipients()
+                            .into_iter()
+                            .map(Mention::from)
+                            .for_each(|mention: Mention| text += &format!("{} ", mention));
+                        text
+                    }
+                    (Some(roles), _) => {
                         let mut text = String::from("Attached roles: ");
                         roles
                             .iter()
@@ -437,23 +892,105 @@ pub(crate) async fn info(
                             .for_each(|mention: Mention| text += &format!("{} ", mention));
                         text
                     }
-                    None => "No roles attached.".to_string(),
+                    (None, _) => "No roles attached.".to_string(),
                 };
 
                 let repeat_info = match repeating {
                     Some(repeat) => {
+                        let sent = match repeat.max_occurrences {
+                            Some(max) => format!("{} of {} sent", repeat.sent(), max),
+                            None => format!("{} sent", repeat.sent()),
+                        };
+                        let ends = match repeat.until {
+                            Some(until) => format!(
+                                ", ends {}",
+                                FormattedTimestamp::new(
+                                    until,
+                                    Some(FormattedTimestampStyle::LongDateTime)
+                                )
+                            ),
+                            None => "".to_string(),
+                        };
+                        let mut skipping = repeat
+                            .skipped
+                            .iter()
+                            .map(DateRange::to_string)
+                            .collect::<Vec<_>>();
+                        if repeat.skip_holidays {
+                            skipping.push("server holidays".to_string());
+                        }
+                        let skipping = match skipping.is_empty() {
+                            true => "".to_string(),
+                            false => format!("\nSkipping: {}", skipping.join(", ")),
+                        };
+                        let time_zone = match repeat.time_zone {
+                            Some(time_zone) => format!("\nTime zone: {}", time_zone),
+                            None => "".to_string(),
+                        };
                         format!(
-                            "Repeating {}\nNext: {}",
-                            repeat.interval,
+                            "Repeating {}\nNext: {}\n{}{}{}{}",
+                            repeat,
                             FormattedTimestamp::new(
                                 repeat.next(&target_date),
                                 Some(FormattedTimestampStyle::RelativeTime)
-                            )
+                            ),
+                            sent,
+                            ends,
+                            skipping,
+                            time_zone
                         )
                     }
                     None => "Single-time".to_string(),
                 };
 
+                let lead_times = match reminder.lead_times.is_empty() {
+                    true => "".to_string(),
+                    false => format!(
+                        "\nEarly reminders: {} before",
+                        reminder
+                            .lead_times
+                            .iter()
+                            .map(|&minutes| datetime::format_minutes(minutes))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                };
+
+                let thread = match reminder.thread {
+                    true => "\nOpens a thread each time it is sent",
+                    false => "",
+                };
+
+                let done = {
+                    let lock = ctx.data().lock().unwrap();
+                    match lock.acknowledgements(id).last() {
+                        Some((due, users)) => format!(
+                            "\nDone with the one due {}: {}",
+                            FormattedTimestamp::new(
+                                *due,
+                                Some(FormattedTimestampStyle::LongDateTime)
+                            ),
+                            users
+                                .iter()
+                                .map(|&user| Mention::from(user).to_string())
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        ),
+                        None => "".to_string(),
+                    }
+                };
+
+                let missed_policy = match reminder.missed {
+                    Some(policy) => policy.to_string(),
+                    None => match key.guild_id() {
+                        Some(guild_id) => format!(
+                            "{} (server default)",
+                            ctx.data().lock().unwrap().missed_policy(guild_id)
+                        ),
+                        None => format!("{} (default)", MissedPolicy::default()),
+                    },
+                };
+
                 let description = format!(
                     "Name: {}\n\
                     Text body: {}\n\n\
@@ -462,7 +999,8 @@ pub(crate) async fn info(
                     Created by: {}\n\
                     \n\
                     Registered for: {}\n\
-                    {}",
+                    {}{}{}{}\n\
+                    If missed: {}",
                     title,
                     text_body,
                     roles,
@@ -476,11 +1014,18 @@ pub(crate) async fn info(
                         Some(FormattedTimestampStyle::LongDateTime)
                     ),
                     repeat_info,
+                    lead_times,
+                    thread,
+                    done,
+                    missed_policy,
                 );
                 embed = embed.description(description);
             } else {
-                embed =
-                    embed.description(format!("Reminder id {} was not found in this channel.", id));
+                embed = embed.description(format!(
+                    "Reminder id {} was not found in {}.",
+                    id,
+                    place(key)
+                ));
             }
         }
     };
@@ -489,3 +1034,428 @@ pub(crate) async fn info(
     ctx.send(reply).await?;
     Ok(())
 }
+
+#[poise::command(slash_command, guild_only)]
+pub(crate) async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "Reminder id, leave empty for the whole channel"] id: Option<String>,
+    #[description = "Target Channel"]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let id = match id.as_deref().map(str::parse::<ReminderId>).transpose() {
+        Ok(id) => id,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let key = match get_key(&ctx, channel, false).await {
+        Ok(key) => key,
+        Err(error) => {
+            reply = reply
+                .content(format!("An error occured: {}", error))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let subscribed = {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .subscribe(key, ctx.author().id, id)
+    };
+    reply = match (subscribed, id) {
+        (Ok(_), Some(id)) => reply.content(format!(
+            "Subscribed! You will get a copy of reminder `{}` by DM.",
+            id
+        )),
+        (Ok(_), None) => reply
+            .content("Subscribed! You will get a copy of every reminder in this channel by DM."),
+        (Err(e), _) => reply.content(e.to_string()),
+    };
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command)]
+pub(crate) async fn unsubscribe(
+    ctx: Context<'_>,
+    #[description = "Reminder id, leave empty for the whole channel"] id: Option<String>,
+    #[description = "Target Channel"]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
+    channel: Option<serenity::GuildChannel>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let id = match id.as_deref().map(str::parse::<ReminderId>).transpose() {
+        Ok(id) => id,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    // personal reminders someone else set for you
+    if let Some(id) = id {
+        let stopped = {
+            ctx.data()
+                .lock()
+                .unwrap()
+                .stop_receiving(ctx.author().id, id)
+        };
+        if stopped.is_ok() {
+            reply = reply
+                .content(format!("You will no longer get reminder `{}`.", id))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    }
+
+    let key = match get_key(&ctx, channel, false).await {
+        Ok(key) => key,
+        Err(error) => {
+            reply = reply
+                .content(format!("An error occured: {}", error))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let unsubscribed = {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .unsubscribe(key, ctx.author().id, id)
+    };
+    reply = match unsubscribed {
+        Ok(_) => reply.content("Unsubscribed!"),
+        Err(e) => reply.content(e.to_string()),
+    };
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command)]
+pub(crate) async fn subscriptions(ctx: Context<'_>) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let lines = {
+        let lock = ctx.data().lock().unwrap();
+        lock.subscriptions(ctx.author().id)
+            .into_iter()
+            .map(|(key, reminder)| {
+                let place = match key {
+                    ReminderKey::Channel(_, channel_id) => {
+                        format!("in {}", Mention::from(channel_id))
+                    }
+                    ReminderKey::User(user_id) => format!("from {}", Mention::from(user_id)),
+                };
+                match reminder {
+                    Some(reminder) => match &reminder.name {
+                        Some(name) => format!("- `{}` ({}) {}", reminder.id(), name, place),
+                        None => format!("- `{}` {}", reminder.id(), place),
+                    },
+                    None => format!("- Every reminder {}", place),
+                }
+            })
+            .collect::<Vec<_>>()
+    };
+
+    reply = match lines.is_empty() {
+        true => reply.content("You are not subscribed to any reminders."),
+        false => reply.embed(
+            CreateEmbed::default()
+                .title("Your subscriptions")
+                .description(lines.join("\n")),
+        ),
+    };
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn missed(
+    ctx: Context<'_>,
+    #[description = "What to do with reminders that were due while the bot was offline"]
+    policy: MissedPolicy,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .set_missed_policy(guild_id, policy);
+    }
+
+    reply = reply
+        .content(format!(
+            "Missed reminders will now use the {} policy.",
+            policy
+        ))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn export(
+    ctx: Context<'_>,
+    #[description = "File format, JSON if not given"] format: Option<ExportFormat>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let export = { GuildExport::from_table(&ctx.data().lock().unwrap(), guild_id) };
+    if export.reminders.is_empty() {
+        reply = reply
+            .content("No reminders have been set in this server.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let format = format.unwrap_or_default();
+    let file = serenity::CreateAttachment::bytes(
+        export.to_string(format)?.into_bytes(),
+        format!("reminders-{}.{}", guild_id, format.extension()),
+    );
+    reply = reply
+        .content(format!(
+            "Exported {} reminders, without who is subscribed to them. Use `/reminder import` to add them to a server.",
+            export.reminders.len()
+        ))
+        .attachment(file)
+        .ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Most problems listed when an import is refused, to keep the reply short
+const MAX_IMPORT_ERRORS: usize = 10;
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn import(
+    ctx: Context<'_>,
+    #[description = "JSON or YAML file from /reminder export"] file: serenity::Attachment,
+    #[description = "Put every reminder in this channel instead"]
+    #[channel_types("Text", "Forum", "PublicThread", "PrivateThread")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Add the reminders, rather than only showing them"] confirm: Option<bool>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    if file.size > MAX_IMPORT_BYTES {
+        reply = reply
+            .content(format!(
+                "The file must be at most {} KiB.",
+                MAX_IMPORT_BYTES / 1024
+            ))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    let export = match file.download().await {
+        Ok(bytes) => GuildExport::parse(&bytes, ExportFormat::from_file_name(&file.filename)),
+        Err(e) => Err(e.into()),
+    };
+    let export = match export {
+        Ok(export) => export,
+        Err(e) => {
+            reply = reply
+                .content(format!("An error occured: {}", e))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    // roles are looked up before any awaits, as the cached guild cannot be held across them
+    let unknown_roles = match ctx.guild() {
+        Some(guild) => export
+            .reminders
+            .iter()
+            .flat_map(|reminder| reminder.roles.iter().copied())
+            .filter(|role_id| !guild.roles.contains_key(role_id))
+            .collect::<HashSet<RoleId>>(),
+        None => HashSet::new(),
+    };
+
+    // every entry is checked first, so a bad file adds nothing
+    let now = Timestamp::now();
+    let mut channels: HashMap<ChannelId, Result<serenity::GuildChannel, String>> = HashMap::new();
+    let mut reminders = Vec::new();
+    let mut errors = Vec::new();
+    for (number, entry) in export.reminders.iter().enumerate().map(|(i, e)| (i + 1, e)) {
+        let target = match &channel {
+            Some(channel) => Ok(channel.clone()),
+            None => match channels.get(&entry.channel) {
+                Some(found) => found.clone(),
+                None => {
+                    let found = import_channel(&ctx, guild_id, entry.channel)
+                        .await
+                        .map_err(|e| e.to_string());
+                    channels.insert(entry.channel, found.clone());
+                    found
+                }
+            },
+        };
+        let checked = target.and_then(|target| {
+            if entry.thread {
+                check_thread(Some(&target)).map_err(|e| e.to_string())?;
+            }
+            if let Some(role_id) = entry.roles.iter().find(|role| unknown_roles.contains(role)) {
+                return Err(format!(
+                    "{} is not a role in this server.",
+                    Mention::from(*role_id)
+                ));
+            }
+            let reminder = entry
+                .to_reminder(ctx.author().id, now)
+                .map_err(|e| e.to_string())?;
+            Ok((ReminderKey::Channel(guild_id, target.id), reminder))
+        });
+        match checked {
+            Ok(checked) => reminders.push(checked),
+            Err(e) => errors.push(format!("- Reminder {}: {}", number, e)),
+        }
+    }
+
+    if export.reminders.is_empty() {
+        reply = reply
+            .content("The file does not have any reminders.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    if !errors.is_empty() {
+        let mut content = format!(
+            "Nothing was imported, as {} of the reminders are invalid:\n",
+            errors.len()
+        );
+        content += &errors[..errors.len().min(MAX_IMPORT_ERRORS)].join("\n");
+        if errors.len() > MAX_IMPORT_ERRORS {
+            content += &format!("\n...and {} more.", errors.len() - MAX_IMPORT_ERRORS);
+        }
+        reply = reply.content(content).ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let confirm = confirm.unwrap_or(false);
+    let mut description = format!("Reminders: {}", reminders.len());
+    if reminders.len() > MAX_FIELDS {
+        description += &format!(", showing the first {}", MAX_FIELDS);
+    }
+    let embed = CreateEmbed::default()
+        .title(match confirm {
+            true => "Imported reminders",
+            false => "Reminders to import",
+        })
+        .description(description)
+        .fields(reminders.iter().take(MAX_FIELDS).map(|(key, reminder)| {
+            let title = reminder.name.clone().unwrap_or("Unnamed".to_string());
+            let location = match key {
+                ReminderKey::Channel(_, channel_id) => {
+                    format!("In {}", Mention::from(*channel_id))
+                }
+                ReminderKey::User(_) => "By DM".to_string(),
+            };
+            (title, format!("{}{}", reminder, location), false)
+        }));
+
+    if confirm {
+        let data = ctx.data();
+        let added = {
+            let mut lock = data.lock().unwrap();
+            reminders
+                .into_iter()
+                .map(|(key, reminder)| lock.add_reminder(key, reminder).map(|added| (key, added)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match added {
+            Ok(added) => {
+                reply = reply.content(format!("Imported {} reminders!", added.len()));
+                for (key, reminder) in added {
+                    data.scheduler.schedule(key, reminder);
+                }
+            }
+            Err(e) => {
+                reply = reply.content(format!("An error occured: {}", e));
+            }
+        }
+    } else {
+        reply = reply.content("Nothing has been added yet. Run again with `confirm` to add these.");
+    }
+
+    reply = reply.embed(embed).ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+/// Finds a channel in the guild that imported reminders can be sent to
+async fn import_channel(
+    ctx: &Context<'_>,
+    guild_id: serenity::GuildId,
+    channel_id: ChannelId,
+) -> Result<serenity::GuildChannel, Error> {
+    let missing = || {
+        format!(
+            "{} is not a channel in this server.",
+            Mention::from(channel_id)
+        )
+    };
+    let channel = match channel_id.to_channel(ctx).await {
+        Ok(channel) => channel.guild().ok_or_else(missing)?,
+        Err(_) => return Err(missing().into()),
+    };
+    if channel.guild_id != guild_id {
+        return Err(missing().into());
+    }
+    match channel.kind {
+        ChannelType::Text
+        | ChannelType::Forum
+        | ChannelType::PublicThread
+        | ChannelType::PrivateThread => Ok(channel),
+        _ => Err(format!("{} cannot have reminders.", Mention::from(channel_id)).into()),
+    }
+}
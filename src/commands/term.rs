@@ -0,0 +1,158 @@
+use chrono::NaiveDate;
+use poise::{serenity_prelude::CreateEmbed, CreateReply};
+
+use crate::{
+    backend::{calendar::Term, data::DateRange},
+    Context, Error,
+};
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("add", "remove", "list"),
+    subcommand_required
+)]
+pub(crate) async fn term(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn add(
+    ctx: Context<'_>,
+    #[description = "Term name without spaces, e.g. Autumn"] name: String,
+    #[description = "First day of week 1, e.g. 2024-09-23"] start: String,
+    // the upper limit is `MAX_WEEKS`, checked by `Term::new` as attributes only take literals
+    #[min = 1]
+    #[description = "Number of teaching weeks"]
+    weeks: u32,
+    #[description = "Breaks, e.g. 2024-11-04..2024-11-10"] breaks: Option<String>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let start = match NaiveDate::parse_from_str(start.trim(), "%Y-%m-%d") {
+        Ok(start) => start,
+        Err(_) => {
+            reply = reply
+                .content(format!(
+                    "\"{}\" is not a date like 2024-09-23.",
+                    start.trim()
+                ))
+                .ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let term = breaks
+        .as_deref()
+        .map(DateRange::parse_list)
+        .transpose()
+        .and_then(|breaks| {
+            Term::new(
+                name.trim().to_string(),
+                start,
+                weeks,
+                breaks.unwrap_or_default(),
+            )
+        });
+    let term = match term {
+        Ok(term) => term,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    let content = format!("Added term {}: {}.", term.name, term);
+    {
+        ctx.data().lock().unwrap().set_term(guild_id, term);
+    }
+
+    reply = reply.content(content).ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn remove(
+    ctx: Context<'_>,
+    #[description = "Term name (from list command)"] name: String,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let removed = {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .remove_term(guild_id, name.trim())
+    };
+    match removed {
+        Ok(_) => {
+            reply = reply.content("Removed!");
+        }
+        Err(e) => {
+            reply = reply.content(e.to_string());
+        }
+    }
+
+    reply = reply.ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only)]
+pub(crate) async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    {
+        let lock = ctx.data().lock().unwrap();
+        match lock.calendar(guild_id) {
+            None => {
+                reply = reply
+                    .content("No terms have been set for this server.")
+                    .ephemeral(true);
+            }
+            Some(calendar) => {
+                let embed = CreateEmbed::default()
+                    .title("Terms")
+                    .description("Reminders can be set for dates like \"T1 W7 Fri 12:00\".")
+                    .fields(calendar.terms().iter().enumerate().map(|(i, term)| {
+                        (
+                            format!("T{} ({})", i + 1, term.name),
+                            term.to_string(),
+                            false,
+                        )
+                    }));
+                reply = reply.embed(embed);
+            }
+        }
+    }
+
+    ctx.send(reply).await?;
+    Ok(())
+}
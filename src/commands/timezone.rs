@@ -0,0 +1,126 @@
+use chrono_tz::Tz;
+use poise::CreateReply;
+
+use crate::{Context, Error};
+
+#[poise::command(
+    slash_command,
+    subcommands("server", "me", "show"),
+    subcommand_required
+)]
+pub(crate) async fn timezone(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+fn parse_time_zone(name: &str) -> Result<Tz, Error> {
+    name.trim().parse::<Tz>().map_err(|_| {
+        format!(
+            "Unknown time zone \"{}\", use a name like Europe/London.",
+            name.trim()
+        )
+        .into()
+    })
+}
+
+#[poise::command(slash_command, guild_only, required_permissions = "MANAGE_GUILD")]
+pub(crate) async fn server(
+    ctx: Context<'_>,
+    #[description = "Time zone name, e.g. Europe/London"] zone: String,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let Some(guild_id) = ctx.guild_id() else {
+        reply = reply
+            .content("This command is only available in servers!")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let zone = match parse_time_zone(&zone) {
+        Ok(zone) => zone,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .set_guild_time_zone(guild_id, zone);
+    }
+
+    reply = reply
+        .content(format!(
+            "Dates and new repeating reminders in this server will now use {}.",
+            zone
+        ))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command)]
+pub(crate) async fn me(
+    ctx: Context<'_>,
+    #[description = "Time zone name, leave empty to use the server's"] zone: Option<String>,
+) -> Result<(), Error> {
+    let mut reply = CreateReply::default();
+
+    let zone = match zone.as_deref().map(parse_time_zone).transpose() {
+        Ok(zone) => zone,
+        Err(e) => {
+            reply = reply.content(e.to_string()).ephemeral(true);
+            ctx.send(reply).await?;
+            return Ok(());
+        }
+    };
+
+    {
+        ctx.data()
+            .lock()
+            .unwrap()
+            .set_user_time_zone(ctx.author().id, zone);
+    }
+
+    let content = match zone {
+        Some(zone) => format!("Your dates and repeating reminders will now use {}.", zone),
+        None => {
+            "Your dates and repeating reminders will now use the server's time zone.".to_string()
+        }
+    };
+    reply = reply.content(content).ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command)]
+pub(crate) async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let content = {
+        let lock = ctx.data().lock().unwrap();
+        let server = match ctx
+            .guild_id()
+            .and_then(|guild_id| lock.guild_time_zone(guild_id))
+        {
+            Some(zone) => zone.to_string(),
+            None => "Not set (UTC)".to_string(),
+        };
+        let user = match lock.user_time_zone(ctx.author().id) {
+            Some(zone) => zone.to_string(),
+            None => "Not set".to_string(),
+        };
+        format!(
+            "Server time zone: {}\nYour time zone: {}\nUsing: {}",
+            server,
+            user,
+            lock.time_zone(ctx.guild_id(), ctx.author().id)
+        )
+    };
+
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
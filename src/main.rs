@@ -1,21 +1,19 @@
 pub(crate) mod backend;
 pub(crate) mod commands;
+pub(crate) mod scheduler;
+#[cfg(test)]
+mod test_util;
 
 use crate::{
-    backend::data::{Reminder, ReminderTable},
-    commands::reminder::reminder,
+    backend::shared::SharedTable,
+    commands::{holiday::holiday, reminder::reminder, term::term, timezone::timezone},
+    scheduler::Scheduler,
 };
 use poise::{
     serenity_prelude::{Cache, Client, FullEvent, GatewayIntents, Http},
     FrameworkContext,
 };
-use std::{
-    collections::HashMap,
-    ops::Deref,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-};
-use tokio::task::JoinHandle;
+use std::{ops::Deref, path::PathBuf, sync::Arc};
 
 // Accept any error type as error type
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -25,25 +23,20 @@ type Data = UserData;
 
 /// All data needed by bot
 struct UserData {
-    pub data: Arc<Mutex<ReminderTable>>,
-    pub tasks: Arc<Mutex<HashMap<Reminder, JoinHandle<()>>>>,
-    pub cache: Arc<Cache>,
-    pub http: Arc<Http>,
+    pub data: Arc<SharedTable>,
+    pub scheduler: Scheduler,
 }
 
 impl UserData {
-    pub fn new(cache: Arc<Cache>, http: Arc<Http>) -> Self {
-        Self {
-            data: Default::default(),
-            tasks: Default::default(),
-            cache,
-            http,
-        }
+    /// Also starts the scheduler for delivering reminders in the table
+    pub fn new(data: Arc<SharedTable>, cache: Arc<Cache>, http: Arc<Http>) -> Self {
+        let scheduler = Scheduler::start((cache, http), data.clone());
+        Self { data, scheduler }
     }
 }
 
 impl Deref for UserData {
-    type Target = Arc<Mutex<ReminderTable>>;
+    type Target = Arc<SharedTable>;
 
     fn deref(&self) -> &Self::Target {
         &self.data
@@ -53,7 +46,7 @@ impl Deref for UserData {
 async fn event_handler(
     event: &FullEvent,
     _: FrameworkContext<'_, UserData, Error>,
-    _: &UserData,
+    data: &UserData,
 ) -> Result<(), Error> {
     match event {
         FullEvent::Ready {
@@ -68,6 +61,12 @@ async fn event_handler(
             }
             Ok(())
         }
+        FullEvent::InteractionCreate { ctx, interaction } => {
+            if let Some(component) = interaction.as_message_component() {
+                commands::buttons::handle_button(ctx, component, data).await?;
+            }
+            Ok(())
+        }
         _ => Ok(()),
     }
 }
@@ -77,20 +76,35 @@ async fn main() {
     let token = std::env::var("DISCORD_TOKEN").expect("Missing DISCORD_TOKEN");
     let intents = GatewayIntents::non_privileged();
 
-    // Load saved reminders from disk
-    let path = PathBuf::from("./reminder_table");
-    let mut loaded_table = match backend::load_data_from_path(&path) {
+    // Load saved reminders from the store chosen with REMINDER_STORE, cbor (default) or sqlite
+    let kind = std::env::var("REMINDER_STORE").unwrap_or_else(|_| "cbor".to_string());
+    let store_path = std::env::var_os("REMINDER_STORE_PATH").map(PathBuf::from);
+    let store = match backend::store::open_store(&kind, store_path) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("Failed to open reminder store: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let loaded_table = match store.load() {
         Ok(table) => table,
-        Err(_) => ReminderTable::new(),
+        Err(e) => {
+            // starting with an empty table would overwrite every reminder on the next save
+            eprintln!("Failed to load reminders: {}", e);
+            eprintln!("Refusing to start so they are not overwritten.");
+            std::process::exit(1);
+        }
     };
 
-    let data = Arc::new(Mutex::new(loaded_table.clone()));
+    // every change to the table is saved shortly after it is made
+    let data = Arc::new(SharedTable::new(loaded_table, store));
+    data.start_saving();
     // clone of data for moving into setup
     let data_i = data.clone();
 
     let framework = poise::Framework::new(
         poise::FrameworkOptions {
-            commands: vec![reminder()],
+            commands: vec![reminder(), holiday(), term(), timezone()],
             event_handler: |event, ctx, data| {
                 Box::pin(async move { event_handler(event, ctx, data).await })
             },
@@ -100,8 +114,12 @@ async fn main() {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 // Create user data with fresh cache and http, but with loaded reminder table
-                let mut user_data = UserData::new(ctx.cache.clone(), ctx.http.clone());
-                user_data.data = data_i;
+                let user_data = UserData::new(data_i, ctx.cache.clone(), ctx.http.clone());
+                // Reminders loaded from disk need to be queued for delivery again
+                {
+                    let lock = user_data.data.lock().unwrap();
+                    user_data.scheduler.schedule_all(&lock);
+                }
                 Ok(user_data)
             })
         },
@@ -113,25 +131,6 @@ async fn main() {
         .unwrap();
     let manager = client.shard_manager.clone();
 
-    // Saves the reminder table to disk every minute if it has changed
-    tokio::spawn(async move {
-        loop {
-            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
-            let copy = {
-                let lock = data.lock().unwrap();
-                lock.clone()
-            };
-
-            if copy != loaded_table {
-                use std::fs::File;
-
-                loaded_table = copy;
-                let file = File::create("reminder_table").expect("Couldn't create file.");
-                serde_cbor::to_writer(file, &loaded_table).expect("Couldn't write to file.");
-            }
-        }
-    });
-
     // Run discord bot client
     tokio::spawn(async move {
         if let Err(e) = client.start_shard(0, 1).await {
@@ -141,11 +140,12 @@ async fn main() {
 
     match tokio::signal::ctrl_c().await {
         Ok(()) => {
-            // Prevents changes from being lost if they were made <60 seconds before shutting down
-            // Not ideal
-            println!("Shutting down. Waiting for 60 seconds...");
+            println!("Shutting down...");
             manager.shutdown_all().await;
-            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+            // save any changes still waiting for the background save
+            if let Err(e) = data.save() {
+                eprintln!("Failed to save reminders: {}", e);
+            }
         }
         Err(err) => {
             eprintln!("Unable to listen for shutdown signal: {}", err);
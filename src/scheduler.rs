@@ -0,0 +1,499 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    sync::Arc,
+    time::Duration,
+};
+
+use poise::serenity_prelude::{Cache, Http, Timestamp};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    backend::{
+        data::{MissedPolicy, Reminder, ReminderId, ReminderKey, ReminderTable},
+        shared::SharedTable,
+    },
+    commands::send_reminder,
+};
+
+/// Requests sent from commands to the scheduler loop
+enum Message {
+    Schedule {
+        key: ReminderKey,
+        reminder: Box<Reminder>,
+    },
+    Cancel {
+        key: ReminderKey,
+        id: ReminderId,
+    },
+}
+
+/// A single pending delivery in the timer queue
+struct Entry {
+    /// Unix timestamp at which the reminder should be sent
+    due: i64,
+    /// Insertion order, keeps entries due at the same time in order
+    seq: u64,
+    key: ReminderKey,
+    id: ReminderId,
+    /// The reminder was due while the bot was offline
+    late: bool,
+    /// Minutes before the deadline, for early reminders
+    lead: Option<u32>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.due, self.seq) == (other.due, other.seq)
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.due, self.seq).cmp(&(other.due, other.seq))
+    }
+}
+
+/// Handle to the scheduler loop.
+/// All reminder deliveries go through a single timer queue instead of one task per reminder.
+#[derive(Clone)]
+pub(crate) struct Scheduler {
+    sender: UnboundedSender<Message>,
+}
+
+impl Scheduler {
+    /// Spawns the scheduler loop. It runs until every handle has been dropped.
+    pub fn start(cache_http: (Arc<Cache>, Arc<Http>), reminders: Arc<SharedTable>) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let state = State {
+            queue: BinaryHeap::new(),
+            seq: 0,
+            cache_http,
+            reminders,
+        };
+        tokio::spawn(state.run(receiver));
+        Self { sender }
+    }
+
+    /// Queue a reminder for delivery at its next due time
+    pub fn schedule(&self, key: ReminderKey, reminder: Reminder) {
+        let _ = self.sender.send(Message::Schedule {
+            key,
+            reminder: Box::new(reminder),
+        });
+    }
+
+    /// Stop a queued reminder from being delivered
+    pub fn cancel(&self, key: ReminderKey, id: ReminderId) {
+        let _ = self.sender.send(Message::Cancel { key, id });
+    }
+
+    /// Queue every reminder in the table.
+    /// Used on startup so reminders loaded from disk are delivered again.
+    pub fn schedule_all(&self, reminders: &ReminderTable) {
+        for (key, set) in reminders.iter() {
+            for reminder in set {
+                self.schedule(key, reminder.clone());
+            }
+        }
+    }
+}
+
+/// State owned by the scheduler loop
+struct State {
+    queue: BinaryHeap<Reverse<Entry>>,
+    seq: u64,
+    cache_http: (Arc<Cache>, Arc<Http>),
+    reminders: Arc<SharedTable>,
+}
+
+impl State {
+    async fn run(mut self, mut receiver: UnboundedReceiver<Message>) {
+        loop {
+            let next_due = self.queue.peek().map(|Reverse(entry)| entry.due);
+            // sleep until the earliest entry is due, if there is one
+            let sleep_duration = next_due
+                .map(|due| Duration::from_secs((due - Timestamp::now().timestamp()).max(0) as u64))
+                .unwrap_or_default();
+
+            tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(Message::Schedule { key, reminder }) => {
+                        self.push(key, *reminder);
+                    }
+                    Some(Message::Cancel { key, id }) => {
+                        self.queue
+                            .retain(|Reverse(entry)| !(entry.key == key && entry.id == id));
+                    }
+                    // every handle has been dropped
+                    None => break,
+                },
+                _ = tokio::time::sleep(sleep_duration), if next_due.is_some() => {
+                    if let Some(Reverse(entry)) = self.queue.pop() {
+                        self.fire(entry);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Works out when a reminder is next due and adds it to the queue.
+    /// Reminders due while the bot was offline are handled according to their missed policy.
+    fn push(&mut self, key: ReminderKey, reminder: Reminder) {
+        let now = Timestamp::now();
+        let policy = match reminder.missed {
+            Some(policy) => policy,
+            // personal reminders have no guild, so use the default policy
+            None => key
+                .guild_id()
+                .map(|guild_id| self.reminders.lock().unwrap().missed_policy(guild_id))
+                .unwrap_or_default(),
+        };
+
+        let (due, late) = match plan(&reminder, policy, now) {
+            Plan::Remove => {
+                let _ = self
+                    .reminders
+                    .lock()
+                    .unwrap()
+                    .remove_reminder(key, reminder.id());
+                return;
+            }
+            Plan::Queue { due, late, skipped } => {
+                if let Some(skipped) = skipped {
+                    // replace old reminder with one with higher repeat count
+                    let _ = self
+                        .reminders
+                        .lock()
+                        .unwrap()
+                        .update_reminder(key, *skipped);
+                }
+                (due, late)
+            }
+        };
+
+        self.seq += 1;
+        self.queue.push(Reverse(Entry {
+            due,
+            seq: self.seq,
+            key,
+            id: reminder.id(),
+            late,
+            lead: None,
+        }));
+
+        // early reminders that are already past are not sent
+        for &lead in &reminder.lead_times {
+            let lead_due = due - lead as i64 * 60;
+            if lead_due <= now.timestamp() {
+                continue;
+            }
+            self.seq += 1;
+            self.queue.push(Reverse(Entry {
+                due: lead_due,
+                seq: self.seq,
+                key,
+                id: reminder.id(),
+                late: false,
+                lead: Some(lead),
+            }));
+        }
+    }
+
+    /// Sends a due reminder and updates the table, queueing the next repeat if there is one
+    fn fire(&mut self, entry: Entry) {
+        let Entry {
+            due,
+            key,
+            id,
+            late,
+            lead,
+            ..
+        } = entry;
+
+        // early reminders only send a message, the reminder itself is still to come
+        if let Some(lead) = lead {
+            let Some(reminder) = self
+                .reminders
+                .lock()
+                .unwrap()
+                .get_reminder(key, id)
+                .cloned()
+            else {
+                return;
+            };
+            let deadline = Timestamp::from_unix_timestamp(due + lead as i64 * 60).ok();
+            self.send(key, reminder, false, deadline);
+            return;
+        }
+
+        // the table is the source of truth, removed reminders are not sent
+        let (reminder, next) = {
+            let mut lock = self.reminders.lock().unwrap();
+            let Some(reminder) = lock.get_reminder(key, id).cloned() else {
+                return;
+            };
+
+            let next = advance(&reminder, Timestamp::now());
+            match &next {
+                // replace old reminder with one with higher repeat count
+                Some(new_reminder) => {
+                    let _ = lock.update_reminder(key, new_reminder.clone());
+                }
+                // if the reminder doesn't repeat it can be removed after its done
+                None => {
+                    let _ = lock.remove_reminder(key, id);
+                }
+            }
+            (reminder, next)
+        };
+
+        self.send(key, reminder, late, None);
+
+        if let Some(new_reminder) = next {
+            self.push(key, new_reminder);
+        }
+    }
+
+    /// Sending is done outside the loop so a slow request does not hold up other reminders
+    fn send(&self, key: ReminderKey, reminder: Reminder, late: bool, deadline: Option<Timestamp>) {
+        let (cache, http) = self.cache_http.clone();
+        let subscribers = self.reminders.lock().unwrap().subscribers(key, &reminder);
+        tokio::spawn(async move {
+            if let Err(e) = send_reminder(
+                key,
+                (&cache, &http),
+                &reminder,
+                &subscribers,
+                late,
+                deadline,
+            )
+            .await
+            {
+                eprintln!("Failed to send reminder in {}: {}", key, e);
+            }
+        });
+    }
+}
+
+/// When a reminder being queued should be sent, if at all
+#[derive(Debug, PartialEq)]
+enum Plan {
+    /// Nothing left to send, so the reminder is removed
+    Remove,
+    Queue {
+        /// Unix timestamp to send it at
+        due: i64,
+        /// It was due while the bot was offline
+        late: bool,
+        /// The reminder moved on past the missed repeats, to store in place of the old one
+        skipped: Option<Box<Reminder>>,
+    },
+}
+
+/// Decides when to send a reminder, applying the missed policy if it is already due at `now`
+fn plan(reminder: &Reminder, policy: MissedPolicy, now: Timestamp) -> Plan {
+    let target = reminder.target_date;
+    // nothing left to send
+    if reminder
+        .repeating
+        .as_ref()
+        .is_some_and(|repeat| repeat.is_finished(&target))
+    {
+        return Plan::Remove;
+    }
+    // if the reminder is repeating, it is possible this is not the first time it is ran.
+    // if so, use the timestamp from its next method instead
+    let due = reminder.next_due().timestamp();
+    if due > now.timestamp() {
+        return Plan::Queue {
+            due,
+            late: false,
+            skipped: None,
+        };
+    }
+
+    match (policy, reminder.repeating.clone()) {
+        (MissedPolicy::Late, _) => Plan::Queue {
+            due,
+            late: true,
+            skipped: None,
+        },
+        (MissedPolicy::Skip, Some(mut repeat)) => {
+            if !repeat.skip_past(&target, &now) || repeat.is_finished(&target) {
+                eprintln!(
+                    "Reminder {} does not repeat past the current time.",
+                    reminder.id()
+                );
+                return Plan::Remove;
+            }
+            let due = repeat.next(&target).timestamp();
+            let mut skipped = reminder.clone();
+            skipped.repeating = Some(repeat);
+            Plan::Queue {
+                due,
+                late: false,
+                skipped: Some(Box::new(skipped)),
+            }
+        }
+        // one-shot reminders cannot be skipped to a later time, so they are discarded too
+        (MissedPolicy::Skip, None) | (MissedPolicy::Discard, _) => Plan::Remove,
+    }
+}
+
+/// The reminder to keep after it has been sent at `now`, or `None` if it is done.
+/// A late reminder stands in for every repeat missed while offline.
+fn advance(reminder: &Reminder, now: Timestamp) -> Option<Reminder> {
+    let mut repeat = reminder.repeating.clone()?;
+    let target = reminder.target_date;
+    repeat.record_sent();
+    repeat.increment_index(&target);
+    // no repeats left, or it would be due again straight away, forever
+    if !repeat.skip_past(&target, &now) || repeat.is_finished(&target) {
+        return None;
+    }
+    let mut new_reminder = reminder.clone();
+    new_reminder.repeating = Some(repeat);
+    Some(new_reminder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use poise::serenity_prelude::UserId;
+
+    use crate::{
+        backend::data::{Interval, Repeat},
+        test_util::{self, at, START},
+    };
+
+    const HOUR: i64 = 3600;
+
+    fn one_off() -> Reminder {
+        test_util::one_off(UserId::new(1), at(START))
+    }
+
+    fn hourly(max_occurrences: Option<u32>) -> Reminder {
+        let mut reminder = one_off();
+        let mut repeat = Repeat::new(Interval::Hourly, 1).unwrap();
+        repeat.max_occurrences = max_occurrences;
+        reminder.repeating = Some(repeat);
+        reminder
+    }
+
+    fn queued(due: i64, late: bool) -> Plan {
+        Plan::Queue {
+            due,
+            late,
+            skipped: None,
+        }
+    }
+
+    #[test]
+    fn queues_future_reminders() {
+        for policy in [
+            MissedPolicy::Late,
+            MissedPolicy::Skip,
+            MissedPolicy::Discard,
+        ] {
+            assert_eq!(
+                plan(&one_off(), policy, at(START - HOUR)),
+                queued(START, false)
+            );
+            assert_eq!(
+                plan(&hourly(None), policy, at(START - HOUR)),
+                queued(START, false)
+            );
+        }
+    }
+
+    #[test]
+    fn late_policy_sends_missed_reminders() {
+        let now = at(START + 3 * HOUR + 60);
+        assert_eq!(
+            plan(&one_off(), MissedPolicy::Late, now),
+            queued(START, true)
+        );
+        assert_eq!(
+            plan(&hourly(None), MissedPolicy::Late, now),
+            queued(START, true)
+        );
+    }
+
+    #[test]
+    fn skip_policy_moves_to_next_repeat() {
+        let now = at(START + 3 * HOUR + 60);
+        let Plan::Queue {
+            due,
+            late: false,
+            skipped: Some(skipped),
+        } = plan(&hourly(None), MissedPolicy::Skip, now)
+        else {
+            panic!("expected the repeat to be skipped forward");
+        };
+        assert_eq!(due, START + 4 * HOUR);
+        assert_eq!(skipped.next_due(), at(due));
+        // skipped repeats were never sent
+        assert_eq!(skipped.repeating.unwrap().sent(), 0);
+
+        // one-off reminders have nothing to skip to
+        assert_eq!(plan(&one_off(), MissedPolicy::Skip, now), Plan::Remove);
+        // nor do repeats that end before now
+        let mut ended = hourly(None);
+        ended.repeating.as_mut().unwrap().until = Some(at(START + 2 * HOUR));
+        assert_eq!(plan(&ended, MissedPolicy::Skip, now), Plan::Remove);
+    }
+
+    #[test]
+    fn discard_policy_removes_missed_reminders() {
+        let now = at(START + 60);
+        assert_eq!(plan(&one_off(), MissedPolicy::Discard, now), Plan::Remove);
+        assert_eq!(
+            plan(&hourly(None), MissedPolicy::Discard, now),
+            Plan::Remove
+        );
+    }
+
+    #[test]
+    fn removes_finished_repeats() {
+        let mut finished = hourly(Some(1));
+        finished.repeating.as_mut().unwrap().record_sent();
+        assert_eq!(
+            plan(&finished, MissedPolicy::Late, at(START - HOUR)),
+            Plan::Remove
+        );
+    }
+
+    #[test]
+    fn advances_repeats_after_sending() {
+        // one-off reminders are done once sent
+        assert_eq!(advance(&one_off(), at(START)), None);
+
+        let sent = advance(&hourly(Some(3)), at(START)).unwrap();
+        let repeat = sent.repeating.as_ref().unwrap();
+        assert_eq!(repeat.sent(), 1);
+        assert_eq!(sent.next_due(), at(START + HOUR));
+
+        let sent = advance(&sent, at(START + HOUR)).unwrap();
+        assert_eq!(sent.next_due(), at(START + 2 * HOUR));
+        // the third time is the last
+        assert_eq!(advance(&sent, at(START + 2 * HOUR)), None);
+    }
+
+    #[test]
+    fn late_repeats_catch_up_after_sending() {
+        // sent late, standing in for the repeats missed in between
+        let sent = advance(&hourly(None), at(START + 3 * HOUR + 60)).unwrap();
+        assert_eq!(sent.repeating.as_ref().unwrap().sent(), 1);
+        assert_eq!(sent.next_due(), at(START + 4 * HOUR));
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the tests of several modules
+
+use std::{fs, path::PathBuf};
+
+use poise::serenity_prelude::{Timestamp, UserId};
+
+use crate::backend::data::Reminder;
+
+/// 2024-01-31 09:00:00 UTC, a Wednesday at the end of a month
+pub(crate) const START: i64 = 1706691600;
+
+pub(crate) fn at(unix: i64) -> Timestamp {
+    Timestamp::from_unix_timestamp(unix).unwrap()
+}
+
+/// Reminder with nothing set but who made it and when it is due
+pub(crate) fn one_off(user_id: UserId, timestamp: Timestamp) -> Reminder {
+    Reminder::one_off(user_id, timestamp, None, None, None)
+}
+
+/// Empty directory for a test to write files in, named after the test and the process
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("reminder-bot-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}